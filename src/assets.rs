@@ -2,7 +2,7 @@ use bevy::{gltf::Gltf, prelude::*};
 use bevy_asset_loader::{AssetCollection, AssetLoader};
 use rand::Rng;
 
-use crate::GameState;
+use crate::{tiles::Tiles, GameState};
 
 pub struct AssetPlugin {
     pub init_state: GameState,
@@ -31,6 +31,58 @@ impl Plugin for AssetPlugin {
 pub struct NatureKitAssets {
     #[asset(path = "models/kenney_nature_kit", folder(typed))]
     folder: Vec<Handle<Gltf>>,
+
+    // Ground tiles
+    #[asset(path = "models/kenney_nature_kit/ground_grass.glb")]
+    pub ground_grass: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/ground_riverTile.glb")]
+    pub ground_water: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/platform_beach.glb")]
+    pub ground_sand: Handle<Gltf>,
+
+    // Trees
+    #[asset(path = "models/kenney_nature_kit/tree_default.glb")]
+    pub tree_default: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/tree_oak.glb")]
+    pub tree_oak: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/tree_fat.glb")]
+    pub tree_fat: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/tree_pineRoundA.glb")]
+    pub tree_pine_round: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/tree_pineSmallA.glb")]
+    pub tree_pine_small: Handle<Gltf>,
+
+    // Decorations
+    #[asset(path = "models/kenney_nature_kit/grass.glb")]
+    pub grass: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/grass_large.glb")]
+    pub grass_large: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/flower_redA.glb")]
+    pub flower_red: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/flower_yellowA.glb")]
+    pub flower_yellow: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/flower_purpleA.glb")]
+    pub flower_purple: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/plant_bush.glb")]
+    pub plant_bush: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/mushroom_red.glb")]
+    pub mushroom_red: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/mushroom_tanGroup.glb")]
+    pub mushroom_tan_group: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/stump_round.glb")]
+    pub stump_round: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/log.glb")]
+    pub log: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/rock_smallA.glb")]
+    pub rock_small: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/stone_smallFlatA.glb")]
+    pub stone_small_flat: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/cactus_short.glb")]
+    pub cactus_short: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/lily_small.glb")]
+    pub lily_small: Handle<Gltf>,
+    #[asset(path = "models/kenney_nature_kit/lily_large.glb")]
+    pub lily_large: Handle<Gltf>,
 }
 
 #[derive(AssetCollection)]
@@ -68,6 +120,57 @@ impl NatureKitAssets {
         let index = rng.gen_range(0..self.folder.len());
         self.folder[index].clone()
     }
+
+    /// Ground model a collapsed tile sits on
+    pub fn tile_ground(&self, tile: Tiles) -> Handle<Gltf> {
+        match tile {
+            Tiles::Sand => self.ground_sand.clone(),
+            Tiles::Grass => self.ground_grass.clone(),
+            Tiles::Water => self.ground_water.clone(),
+            Tiles::Forest => self.ground_grass.clone(),
+        }
+    }
+
+    /// Main features of a tile, always placed (e.g. the trees of a forest)
+    pub fn tile_features(&self, tile: Tiles) -> Vec<Handle<Gltf>> {
+        match tile {
+            Tiles::Forest => vec![
+                self.tree_default.clone(),
+                self.tree_oak.clone(),
+                self.tree_fat.clone(),
+                self.tree_pine_round.clone(),
+                self.tree_pine_small.clone(),
+            ],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Optional props scattered on top of a tile
+    pub fn tile_decorations(&self, tile: Tiles) -> Vec<Handle<Gltf>> {
+        match tile {
+            Tiles::Sand => vec![
+                self.rock_small.clone(),
+                self.stone_small_flat.clone(),
+                self.cactus_short.clone(),
+            ],
+            Tiles::Grass => vec![
+                self.grass.clone(),
+                self.grass_large.clone(),
+                self.flower_red.clone(),
+                self.flower_yellow.clone(),
+                self.flower_purple.clone(),
+                self.plant_bush.clone(),
+            ],
+            Tiles::Water => vec![self.lily_small.clone(), self.lily_large.clone()],
+            Tiles::Forest => vec![
+                self.mushroom_red.clone(),
+                self.mushroom_tan_group.clone(),
+                self.stump_round.clone(),
+                self.log.clone(),
+                self.grass.clone(),
+            ],
+        }
+    }
 }
 
 pub struct UiColors {
@@ -0,0 +1,112 @@
+use std::sync::Arc;
+
+use super::{Constraint, Contradiction, Domain, Domains, VarId};
+
+/// Every variable takes a different value
+pub struct AllDifferent {
+    vars: Vec<VarId>,
+}
+
+impl AllDifferent {
+    pub fn new(vars: Vec<VarId>) -> Self {
+        Self { vars }
+    }
+}
+
+impl Constraint for AllDifferent {
+    fn scope(&self) -> &[VarId] {
+        &self.vars
+    }
+
+    fn propagate(&self, domains: &mut Domains) -> Result<(), Contradiction> {
+        loop {
+            let mut changed = false;
+
+            // a decided value can't be used by anyone else
+            for &var in self.vars.iter() {
+                if let Some(value) = domains.get(var).value() {
+                    for &other in self.vars.iter() {
+                        if other != var {
+                            changed |= domains.remove(other, value)?;
+                        }
+                    }
+                }
+            }
+
+            // not enough values to go around
+            let union = self
+                .vars
+                .iter()
+                .fold(Domain::empty(), |acc, &v| acc | domains.get(v));
+            if union.len() < self.vars.len() {
+                return Err(Contradiction { var: self.vars[0] });
+            }
+
+            // when every value has to be used, a value only one variable can take is its
+            if union.len() == self.vars.len() {
+                for value in union.iter() {
+                    let mut holders = self
+                        .vars
+                        .iter()
+                        .filter(|&&v| domains.get(v).contains(value));
+                    let holder = *holders.next().unwrap();
+                    if holders.next().is_none() {
+                        changed |= domains.assign(holder, value)?;
+                    }
+                }
+            }
+
+            if !changed {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Two variables whose values have to be compatible, `supports[v]` lists the values `b`
+/// may take when `a` is `v`
+pub struct Compatible {
+    vars: [VarId; 2],
+    supports: Arc<Vec<Domain>>,
+}
+
+impl Compatible {
+    pub fn new(a: VarId, b: VarId, supports: impl Into<Arc<Vec<Domain>>>) -> Self {
+        Self {
+            vars: [a, b],
+            supports: supports.into(),
+        }
+    }
+}
+
+impl Constraint for Compatible {
+    fn scope(&self) -> &[VarId] {
+        &self.vars
+    }
+
+    fn propagate(&self, domains: &mut Domains) -> Result<(), Contradiction> {
+        let [a, b] = self.vars;
+        loop {
+            // b can only take values something in a allows
+            let allowed_b = domains
+                .get(a)
+                .iter()
+                .fold(Domain::empty(), |acc, v| acc | self.supports[v]);
+            let mut changed = domains.restrict(b, allowed_b)?;
+
+            // a can only keep values that still allow something in b
+            let domain_b = domains.get(b);
+            let allowed_a = Domain::from_values(
+                domains
+                    .get(a)
+                    .iter()
+                    .filter(|&v| !(self.supports[v] & domain_b).is_empty()),
+            );
+            changed |= domains.restrict(a, allowed_a)?;
+
+            if !changed {
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::{
+    fmt::Debug,
+    ops::{BitAnd, BitOr, Not, Sub},
+};
+
+/// Set of possible values for a variable, values are indices 0..64
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Domain(pub u64);
+
+impl Domain {
+    pub const MAX_VALUES: usize = 64;
+
+    pub const fn empty() -> Self {
+        Domain(0)
+    }
+
+    /// Domain holding values 0..count
+    pub fn full(count: usize) -> Self {
+        debug_assert!(count <= Self::MAX_VALUES);
+        if count >= Self::MAX_VALUES {
+            Domain(u64::MAX)
+        } else {
+            Domain((1u64 << count) - 1)
+        }
+    }
+
+    pub fn single(value: usize) -> Self {
+        debug_assert!(value < Self::MAX_VALUES);
+        Domain(1u64 << value)
+    }
+
+    pub fn from_values(values: impl IntoIterator<Item = usize>) -> Self {
+        values
+            .into_iter()
+            .fold(Domain::empty(), |d, v| d | Domain::single(v))
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn is_singleton(&self) -> bool {
+        self.0 != 0 && self.0 & (self.0 - 1) == 0
+    }
+
+    pub fn contains(&self, value: usize) -> bool {
+        value < Self::MAX_VALUES && self.0 & (1u64 << value) != 0
+    }
+
+    pub fn insert(&mut self, value: usize) {
+        self.0 |= Domain::single(value).0;
+    }
+
+    pub fn remove(&mut self, value: usize) {
+        self.0 &= !Domain::single(value).0;
+    }
+
+    /// The value if only one is left
+    pub fn value(&self) -> Option<usize> {
+        if self.is_singleton() {
+            Some(self.0.trailing_zeros() as usize)
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> DomainIter {
+        DomainIter(self.0)
+    }
+}
+
+impl BitAnd for Domain {
+    type Output = Domain;
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Domain(self.0 & rhs.0)
+    }
+}
+
+impl BitOr for Domain {
+    type Output = Domain;
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Domain(self.0 | rhs.0)
+    }
+}
+
+impl Sub for Domain {
+    type Output = Domain;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Domain(self.0 & !rhs.0)
+    }
+}
+
+impl Not for Domain {
+    type Output = Domain;
+    fn not(self) -> Self::Output {
+        Domain(!self.0)
+    }
+}
+
+impl Debug for Domain {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+pub struct DomainIter(u64);
+
+impl Iterator for DomainIter {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0 == 0 {
+            return None;
+        }
+        let value = self.0.trailing_zeros() as usize;
+        self.0 &= self.0 - 1;
+        Some(value)
+    }
+}
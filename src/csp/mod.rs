@@ -0,0 +1,500 @@
+// Finite domain constraint solver, shared by the wave function collapse and sudoku
+//
+// Variables have a bitset domain of up to 64 values, constraints narrow those domains
+// and the solver searches by picking the variable with the fewest values left (MRV),
+// backtracking when propagation empties a domain.
+mod constraints;
+mod domain;
+
+use rand::Rng;
+
+pub use constraints::*;
+pub use domain::*;
+
+pub type VarId = usize;
+
+/// A domain was narrowed down to nothing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Contradiction {
+    pub var: VarId,
+}
+
+/// Values removed from a variable during propagation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Narrowed {
+    pub var: VarId,
+    pub removed: Domain,
+}
+
+/// Current domain of every variable, records every narrowing so callers can react to it
+#[derive(Clone, Debug, Default)]
+pub struct Domains {
+    values: Vec<Domain>,
+    changes: Vec<Narrowed>,
+}
+
+impl Domains {
+    pub fn get(&self, var: VarId) -> Domain {
+        self.values[var]
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (VarId, Domain)> + '_ {
+        self.values.iter().copied().enumerate()
+    }
+
+    /// Keep only the allowed values, returns true if anything was removed
+    pub fn restrict(&mut self, var: VarId, allowed: Domain) -> Result<bool, Contradiction> {
+        let current = self.values[var];
+        let next = current & allowed;
+        if next == current {
+            return Ok(false);
+        }
+
+        self.values[var] = next;
+        self.changes.push(Narrowed {
+            var,
+            removed: current - next,
+        });
+
+        if next.is_empty() {
+            Err(Contradiction { var })
+        } else {
+            Ok(true)
+        }
+    }
+
+    pub fn remove(&mut self, var: VarId, value: usize) -> Result<bool, Contradiction> {
+        self.restrict(var, !Domain::single(value))
+    }
+
+    pub fn assign(&mut self, var: VarId, value: usize) -> Result<bool, Contradiction> {
+        self.restrict(var, Domain::single(value))
+    }
+}
+
+pub trait Constraint: Send + Sync {
+    /// Variables this constraint looks at, it is re-run whenever one of them changes
+    fn scope(&self) -> &[VarId];
+
+    /// Narrow the domains of the variables in scope
+    fn propagate(&self, domains: &mut Domains) -> Result<(), Contradiction>;
+}
+
+#[derive(Default)]
+pub struct Problem {
+    initial: Vec<Domain>,
+    constraints: Vec<Box<dyn Constraint>>,
+    // constraints to re-run when a variable changes
+    watchers: Vec<Vec<usize>>,
+}
+
+impl Problem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_variable(&mut self, domain: Domain) -> VarId {
+        self.initial.push(domain);
+        self.watchers.push(Vec::new());
+        self.initial.len() - 1
+    }
+
+    pub fn add_constraint(&mut self, constraint: impl Constraint + 'static) {
+        let index = self.constraints.len();
+        for &var in constraint.scope() {
+            self.watchers[var].push(index);
+        }
+        self.constraints.push(Box::new(constraint));
+    }
+
+    pub fn variable_count(&self) -> usize {
+        self.initial.len()
+    }
+
+    pub fn constraint_count(&self) -> usize {
+        self.constraints.len()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// Picked a value for a variable and propagated it
+    Assigned { var: VarId, value: usize },
+    /// Assigning the value emptied the domain of `at`, the solver backtracked to an
+    /// earlier choice so every domain may have changed
+    Backtracked { var: VarId, value: usize, at: VarId },
+    Solved,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SolverStats {
+    pub assignments: usize,
+    pub contradictions: usize,
+    pub backtracks: usize,
+}
+
+struct Choice {
+    var: VarId,
+    snapshot: Vec<Domain>,
+    // values not yet tried for var
+    remaining: Domain,
+}
+
+pub struct Solver {
+    problem: Problem,
+    domains: Domains,
+    choices: Vec<Choice>,
+    failed: bool,
+    pub stats: SolverStats,
+}
+
+impl Default for Solver {
+    fn default() -> Self {
+        Solver::new(Problem::new())
+    }
+}
+
+impl Solver {
+    pub fn new(problem: Problem) -> Self {
+        let domains = Domains {
+            values: problem.initial.clone(),
+            changes: Vec::new(),
+        };
+        let mut solver = Self {
+            problem,
+            domains,
+            choices: Vec::new(),
+            failed: false,
+            stats: SolverStats::default(),
+        };
+
+        // run every constraint once so the initial domains are consistent
+        let all = (0..solver.problem.constraints.len()).collect::<Vec<_>>();
+        if solver.propagate_constraints(all).is_err() {
+            solver.failed = true;
+        }
+        solver
+    }
+
+    pub fn domain(&self, var: VarId) -> Domain {
+        self.domains.get(var)
+    }
+
+    pub fn domains(&self) -> &Domains {
+        &self.domains
+    }
+
+    pub fn variable_count(&self) -> usize {
+        self.domains.len()
+    }
+
+    pub fn is_solved(&self) -> bool {
+        !self.failed && self.domains.values.iter().all(|d| d.is_singleton())
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.failed
+    }
+
+    /// Narrowings since the last call, cleared on backtrack
+    pub fn take_changes(&mut self) -> Vec<Narrowed> {
+        std::mem::take(&mut self.domains.changes)
+    }
+
+    /// Narrow a variable outside of the search (e.g. sudoku givens), a contradiction here
+    /// can't be undone and fails the solver
+    pub fn restrict(&mut self, var: VarId, allowed: Domain) -> Result<(), Contradiction> {
+        let result = self
+            .domains
+            .restrict(var, allowed)
+            .and_then(|_| self.propagate_from(var));
+        if result.is_err() {
+            self.failed = true;
+        }
+        result
+    }
+
+    pub fn assign(&mut self, var: VarId, value: usize) -> Result<(), Contradiction> {
+        self.restrict(var, Domain::single(value))
+    }
+
+    /// Undecided variable with the fewest values left, ties broken at random
+    pub fn select_variable(&self, rng: &mut impl Rng) -> Option<VarId> {
+        let mut lowest = usize::MAX;
+        let mut seen = 0;
+        let mut selected = None;
+        for (var, domain) in self.domains.iter() {
+            let len = domain.len();
+            if len <= 1 {
+                continue;
+            }
+            if len < lowest {
+                lowest = len;
+                seen = 1;
+                selected = Some(var);
+            } else if len == lowest {
+                // reservoir sample so every tied variable has the same chance
+                seen += 1;
+                if rng.gen_range(0..seen) == 0 {
+                    selected = Some(var);
+                }
+            }
+        }
+        selected
+    }
+
+    /// Make a single decision, picking the value at random
+    pub fn step(&mut self, rng: &mut impl Rng) -> Step {
+        if self.failed {
+            return Step::Failed;
+        }
+        let var = match self.select_variable(rng) {
+            Some(var) => var,
+            None => return Step::Solved,
+        };
+        let domain = self.domains.get(var);
+        let value = domain.iter().nth(rng.gen_range(0..domain.len())).unwrap();
+        self.decide(var, value)
+    }
+
+    /// Try a value for a variable, remembering the alternatives for backtracking
+    pub fn decide(&mut self, var: VarId, value: usize) -> Step {
+        if self.failed {
+            return Step::Failed;
+        }
+        let domain = self.domains.get(var);
+        debug_assert!(domain.contains(value));
+
+        self.choices.push(Choice {
+            var,
+            snapshot: self.domains.values.clone(),
+            remaining: domain - Domain::single(value),
+        });
+        self.stats.assignments += 1;
+
+        let result = self
+            .domains
+            .assign(var, value)
+            .and_then(|_| self.propagate_from(var));
+        match result {
+            Ok(()) => Step::Assigned { var, value },
+            Err(contradiction) => {
+                self.stats.contradictions += 1;
+                if self.backtrack() {
+                    Step::Backtracked {
+                        var,
+                        value,
+                        at: contradiction.var,
+                    }
+                } else {
+                    Step::Failed
+                }
+            }
+        }
+    }
+
+    /// Run steps until solved or out of options
+    pub fn solve(&mut self, rng: &mut impl Rng) -> bool {
+        loop {
+            match self.step(rng) {
+                Step::Solved => return true,
+                Step::Failed => return false,
+                _ => {}
+            }
+        }
+    }
+
+    /// Solved value of each variable, if solved
+    pub fn solution(&self) -> Option<Vec<usize>> {
+        if !self.is_solved() {
+            return None;
+        }
+        Some(self.domains.values.iter().map(|d| d.value().unwrap()).collect())
+    }
+
+    // Undo choices until one has a value left to try
+    fn backtrack(&mut self) -> bool {
+        while let Some(choice) = self.choices.pop() {
+            self.stats.backtracks += 1;
+            self.domains.values = choice.snapshot;
+            self.domains.changes.clear();
+
+            if choice.remaining.is_empty() {
+                continue;
+            }
+
+            // the value we tried failed in this state, so drop it and carry on
+            let result = self
+                .domains
+                .restrict(choice.var, choice.remaining)
+                .and_then(|_| self.propagate_from(choice.var));
+            if result.is_ok() {
+                return true;
+            }
+            self.stats.contradictions += 1;
+        }
+        self.failed = true;
+        false
+    }
+
+    fn propagate_from(&mut self, var: VarId) -> Result<(), Contradiction> {
+        let queue = self.problem.watchers[var].clone();
+        self.propagate_constraints(queue)
+    }
+
+    // AC-3 style, re-run constraints watching any variable that changed until nothing does
+    fn propagate_constraints(&mut self, mut queue: Vec<usize>) -> Result<(), Contradiction> {
+        let mut queued = vec![false; self.problem.constraints.len()];
+        for &c in queue.iter() {
+            queued[c] = true;
+        }
+
+        while let Some(c) = queue.pop() {
+            queued[c] = false;
+            let before = self.domains.changes.len();
+            self.problem.constraints[c].propagate(&mut self.domains)?;
+
+            for i in before..self.domains.changes.len() {
+                let var = self.domains.changes[i].var;
+                for &watcher in self.problem.watchers[var].iter() {
+                    if !queued[watcher] {
+                        queued[watcher] = true;
+                        queue.push(watcher);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn domain_ops() {
+        let mut d = Domain::full(4);
+        assert_eq!(d.len(), 4);
+        d.remove(2);
+        assert_eq!(d.iter().collect::<Vec<_>>(), vec![0, 1, 3]);
+        assert!(!d.is_singleton());
+        assert_eq!((d & Domain::single(3)).value(), Some(3));
+        assert!(Domain::empty().is_empty());
+    }
+
+    #[test]
+    fn all_different_pigeonhole_fails() {
+        let mut problem = Problem::new();
+        let vars = (0..3)
+            .map(|_| problem.add_variable(Domain::full(2)))
+            .collect::<Vec<_>>();
+        problem.add_constraint(AllDifferent::new(vars));
+        let mut solver = Solver::new(problem);
+        assert!(!solver.solve(&mut StdRng::seed_from_u64(0)));
+    }
+
+    fn not_equal(count: usize) -> Vec<Domain> {
+        (0..count).map(|v| Domain::full(count) - Domain::single(v)).collect()
+    }
+
+    #[test]
+    fn compatible_chain_is_consistent() {
+        // 0 - 1 - 2 path, a value may only sit next to its neighbours
+        let supports = vec![
+            Domain::from_values([1]),
+            Domain::from_values([0, 2]),
+            Domain::from_values([1]),
+        ];
+        for seed in 0..20 {
+            let mut problem = Problem::new();
+            let vars = (0..8)
+                .map(|_| problem.add_variable(Domain::full(3)))
+                .collect::<Vec<_>>();
+            for pair in vars.windows(2) {
+                problem.add_constraint(Compatible::new(pair[0], pair[1], supports.clone()));
+            }
+
+            let mut solver = Solver::new(problem);
+            assert!(solver.solve(&mut StdRng::seed_from_u64(seed)));
+            let solution = solver.solution().unwrap();
+            for pair in solution.windows(2) {
+                assert!(supports[pair[0]].contains(pair[1]));
+            }
+        }
+    }
+
+    #[test]
+    fn all_different_finds_hidden_single() {
+        let mut problem = Problem::new();
+        let a = problem.add_variable(Domain::full(2));
+        let b = problem.add_variable(Domain::full(2));
+        let c = problem.add_variable(Domain::full(3));
+        problem.add_constraint(AllDifferent::new(vec![a, b, c]));
+
+        let solver = Solver::new(problem);
+        assert_eq!(solver.domain(c), Domain::single(2));
+    }
+
+    #[test]
+    fn backtracks_out_of_bad_choice() {
+        // triangle of not-equal pairs, b and c only have two values so a must take the third,
+        // pairwise propagation can't see that until a value is tried
+        let mut problem = Problem::new();
+        let a = problem.add_variable(Domain::full(3));
+        let b = problem.add_variable(Domain::full(2));
+        let c = problem.add_variable(Domain::full(2));
+        for (x, y) in [(a, b), (b, c), (a, c)] {
+            problem.add_constraint(Compatible::new(x, y, not_equal(3)));
+        }
+
+        let mut solver = Solver::new(problem);
+        assert_eq!(solver.domain(a), Domain::full(3));
+        assert!(matches!(solver.decide(a, 0), Step::Backtracked { var, .. } if var == a));
+        assert!(solver.solve(&mut StdRng::seed_from_u64(0)));
+        assert_eq!(solver.solution().unwrap()[a], 2);
+        assert!(solver.stats.backtracks > 0);
+    }
+
+    #[test]
+    fn unsatisfiable_fails() {
+        let mut problem = Problem::new();
+        let vars = (0..3)
+            .map(|_| problem.add_variable(Domain::full(2)))
+            .collect::<Vec<_>>();
+        for (x, y) in [(0, 1), (1, 2), (0, 2)] {
+            problem.add_constraint(Compatible::new(vars[x], vars[y], not_equal(2)));
+        }
+
+        let mut solver = Solver::new(problem);
+        assert!(!solver.solve(&mut StdRng::seed_from_u64(0)));
+        assert!(solver.is_failed());
+    }
+
+    #[test]
+    fn decide_records_changes() {
+        let mut problem = Problem::new();
+        let a = problem.add_variable(Domain::full(3));
+        let b = problem.add_variable(Domain::full(3));
+        problem.add_constraint(AllDifferent::new(vec![a, b]));
+        let mut solver = Solver::new(problem);
+
+        assert_eq!(solver.decide(a, 1), Step::Assigned { var: a, value: 1 });
+        let changes = solver.take_changes();
+        assert!(changes.contains(&Narrowed {
+            var: b,
+            removed: Domain::single(1)
+        }));
+        assert!(solver.take_changes().is_empty());
+    }
+}
@@ -1,6 +1,7 @@
 #![allow(warnings)]
 
 mod assets;
+mod csp;
 mod states;
 mod systems;
 
@@ -54,13 +55,14 @@ fn main() {
         .insert_resource(ClearColor(Color::rgb(0.3, 0.3, 0.3)))
         // 3rd Party
         .add_plugins(DefaultPickingPlugins)
-        .add_plugin(PhysicsPlugin)
+        .insert_resource(PhysicsTimestep::new(TIME_STEP))
+        .add_plugin(PhysicsPlugin::default())
         //.add_plugin(HanabiPlugin)
         .add_plugin(TweeningPlugin)
         //.add_plugin(TextMeshPlugin)
         //.add_plugin(EditorPlugin)
         //.add_plugin(RapierPhysicsPlugin::<NoUserData>::pixels_per_meter(10.0))
-        //.add_plugin(RapierDebugRenderPlugin::default())
+        .add_plugin(PhysicsDebugPlugin::default())
         .add_plugin(WorldInspectorPlugin::new())
         // Local Plugins
         .add_plugin(EnviromentPlugin)
@@ -75,7 +77,7 @@ fn main() {
         .add_plugin(TetrisPlugin)
         .add_plugin(OverworldPlugin)
         .add_plugin(BreakoutPlugin)
-        //.add_plugin(WavePlugin)
+        .add_plugin(WavePlugin)
         //.add_plugins(bevy_mod_picking::DefaultPickingPlugins)
         //.add_plugin(bevy_transform_gizmo::TransformGizmoPlugin)
         // Global Setup
@@ -0,0 +1,137 @@
+use bevy::prelude::*;
+
+use super::Collider;
+
+// Planes go on forever, their bounds only need to cover any level
+const PLANE_EXTENT: f32 = 1.0e4;
+
+/// Axis aligned bounding box in world space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    pub fn from_center(center: Vec3, half: Vec3) -> Self {
+        Self {
+            min: center - half,
+            max: center + half,
+        }
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn grow(&self, margin: f32) -> Aabb {
+        Aabb {
+            min: self.min - Vec3::splat(margin),
+            max: self.max + Vec3::splat(margin),
+        }
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.max).all() && self.max.cmpge(other.min).all()
+    }
+
+    pub fn contains(&self, other: &Aabb) -> bool {
+        self.min.cmple(other.min).all() && self.max.cmpge(other.max).all()
+    }
+
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.min.cmple(point).all() && self.max.cmpge(point).all()
+    }
+
+    /// Distances along a ray where it enters and leaves the box, dir need not be normalized
+    pub fn ray_interval(&self, origin: Vec3, dir: Vec3, max_toi: f32) -> Option<(f32, f32)> {
+        let mut enter = 0.0_f32;
+        let mut exit = max_toi;
+        for i in 0..3 {
+            if dir[i].abs() < f32::EPSILON {
+                // parallel to the slab, has to start inside it
+                if origin[i] < self.min[i] || origin[i] > self.max[i] {
+                    return None;
+                }
+                continue;
+            }
+            let inv = dir[i].recip();
+            let (t0, t1) = ((self.min[i] - origin[i]) * inv, (self.max[i] - origin[i]) * inv);
+            enter = enter.max(t0.min(t1));
+            exit = exit.min(t0.max(t1));
+            if enter > exit {
+                return None;
+            }
+        }
+        Some((enter, exit))
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        let d = (self.max - self.min).max(Vec3::ZERO);
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+}
+
+impl Collider {
+    /// Bounds of the collider placed by a transform
+    pub fn aabb(&self, trans: &Transform) -> Aabb {
+        match self {
+            Collider::Sphere { radius } => {
+                Aabb::from_center(trans.translation, Vec3::splat(*radius))
+            }
+            Collider::Cuboid { size } => {
+                let rot = Mat3::from_quat(trans.rotation);
+                let half = rot.x_axis.abs() * size.x
+                    + rot.y_axis.abs() * size.y
+                    + rot.z_axis.abs() * size.z;
+                Aabb::from_center(trans.translation, half)
+            }
+            Collider::Capsule {
+                half_height,
+                radius,
+            } => {
+                let half = (trans.rotation * Vec3::Y * *half_height).abs() + Vec3::splat(*radius);
+                Aabb::from_center(trans.translation, half)
+            }
+            Collider::Plane { .. } => {
+                Aabb::from_center(trans.translation, Vec3::splat(PLANE_EXTENT))
+            }
+            Collider::ConvexHull(hull) => {
+                let mut points = hull
+                    .points()
+                    .iter()
+                    .map(|p| trans.translation + trans.rotation * *p);
+                let first = points.next().unwrap_or(trans.translation);
+                points.fold(Aabb::new(first, first), |aabb, p| {
+                    Aabb::new(aabb.min.min(p), aabb.max.max(p))
+                })
+            }
+            Collider::TriMesh(mesh) => {
+                // bounds of the local bounds turned with the mesh
+                let local = mesh.bounds();
+                let half = (local.max - local.min) * 0.5;
+                let rot = Mat3::from_quat(trans.rotation);
+                let half = rot.x_axis.abs() * half.x
+                    + rot.y_axis.abs() * half.y
+                    + rot.z_axis.abs() * half.z;
+                Aabb::from_center(trans.translation + trans.rotation * local.center(), half)
+            }
+            Collider::Compound(_) => self
+                .parts(trans)
+                .map(|(_, collider, part_trans)| collider.aabb(&part_trans))
+                .reduce(|a, b| a.union(&b))
+                .unwrap_or_else(|| Aabb::new(trans.translation, trans.translation)),
+        }
+    }
+}
@@ -0,0 +1,476 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use super::Aabb;
+
+// Number of buckets tried along an axis when splitting with the SAH
+const SAH_BINS: usize = 12;
+
+#[derive(Debug, Clone)]
+enum NodeKind {
+    Leaf { entity: Entity, tight: Aabb },
+    Branch { children: [usize; 2] },
+}
+
+#[derive(Debug, Clone)]
+struct Node {
+    /// Leaves store fattened bounds, branches the union of their children
+    aabb: Aabb,
+    parent: Option<usize>,
+    kind: NodeKind,
+}
+
+/// Dynamic bounding volume hierarchy over entity bounds. Leaves are fattened by a margin so
+/// small movements don't touch the tree, bulk inserts are rebuilt top down using the surface
+/// area heuristic
+#[derive(Debug, Clone)]
+pub struct Bvh {
+    pub margin: f32,
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    root: Option<usize>,
+    leaves: HashMap<Entity, usize>,
+    // leaves inserted one at a time since the last full build
+    inserted: usize,
+}
+
+impl Default for Bvh {
+    fn default() -> Self {
+        Bvh::new(0.1)
+    }
+}
+
+impl Bvh {
+    pub fn new(margin: f32) -> Self {
+        Self {
+            margin,
+            nodes: Vec::new(),
+            free: Vec::new(),
+            root: None,
+            leaves: HashMap::default(),
+            inserted: 0,
+        }
+    }
+
+    /// Builds a tree over all the items at once
+    pub fn build(margin: f32, items: impl IntoIterator<Item = (Entity, Aabb)>) -> Self {
+        let mut bvh = Bvh::new(margin);
+        for (entity, aabb) in items {
+            let leaf = bvh.alloc_leaf(entity, aabb);
+            bvh.leaves.insert(entity, leaf);
+        }
+        bvh.rebuild();
+        bvh
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.leaves.contains_key(&entity)
+    }
+
+    /// Tight bounds last given for an entity
+    pub fn aabb(&self, entity: Entity) -> Option<Aabb> {
+        let leaf = self.leaves.get(&entity)?;
+        match self.nodes[*leaf].kind {
+            NodeKind::Leaf { tight, .. } => Some(tight),
+            NodeKind::Branch { .. } => None,
+        }
+    }
+
+    pub fn insert(&mut self, entity: Entity, aabb: Aabb) {
+        if self.contains(entity) {
+            self.update(entity, aabb);
+            return;
+        }
+        let leaf = self.alloc_leaf(entity, aabb);
+        self.leaves.insert(entity, leaf);
+        self.insert_leaf(leaf);
+        self.inserted += 1;
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        if let Some(leaf) = self.leaves.remove(&entity) {
+            self.remove_leaf(leaf);
+            self.free.push(leaf);
+        }
+    }
+
+    /// Moves an entity, returns true if it left its fattened bounds and was reinserted
+    pub fn update(&mut self, entity: Entity, aabb: Aabb) -> bool {
+        let leaf = match self.leaves.get(&entity) {
+            Some(leaf) => *leaf,
+            None => {
+                self.insert(entity, aabb);
+                return true;
+            }
+        };
+        self.nodes[leaf].kind = NodeKind::Leaf {
+            entity,
+            tight: aabb,
+        };
+        if self.nodes[leaf].aabb.contains(&aabb) {
+            return false;
+        }
+
+        self.remove_leaf(leaf);
+        self.nodes[leaf].aabb = aabb.grow(self.margin);
+        self.insert_leaf(leaf);
+        self.inserted += 1;
+        true
+    }
+
+    /// Rebuilds the tree top down if most of it was built by single inserts
+    pub fn optimize(&mut self) {
+        if self.inserted > 8 && self.inserted * 2 > self.len() {
+            self.rebuild();
+        }
+    }
+
+    /// Throws away the branches and builds the whole tree again using the SAH
+    pub fn rebuild(&mut self) {
+        let mut leaves = self.leaves.values().copied().collect::<Vec<_>>();
+        // keep builds stable regardless of hash order
+        leaves.sort_unstable();
+
+        // every node that isn't a live leaf can be reused
+        let mut live = vec![false; self.nodes.len()];
+        for leaf in leaves.iter() {
+            live[*leaf] = true;
+        }
+        self.free = (0..self.nodes.len()).filter(|i| !live[*i]).rev().collect();
+
+        self.root = if leaves.is_empty() {
+            None
+        } else {
+            Some(self.build_node(&mut leaves, None))
+        };
+        self.inserted = 0;
+    }
+
+    /// Entities whose bounds overlap the aabb
+    pub fn query(&self, aabb: &Aabb) -> Vec<Entity> {
+        let mut hits = Vec::new();
+        let mut stack = self.root.into_iter().collect::<Vec<_>>();
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            if !node.aabb.intersects(aabb) {
+                continue;
+            }
+            match node.kind {
+                NodeKind::Leaf { entity, tight } => {
+                    if tight.intersects(aabb) {
+                        hits.push(entity);
+                    }
+                }
+                NodeKind::Branch { children } => stack.extend(children),
+            }
+        }
+        hits
+    }
+
+    /// Bounds of every node in the tree, branches and the fattened leaves
+    pub fn node_bounds(&self) -> Vec<Aabb> {
+        let mut bounds = Vec::new();
+        let mut stack = self.root.into_iter().collect::<Vec<_>>();
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            bounds.push(node.aabb);
+            if let NodeKind::Branch { children } = node.kind {
+                stack.extend(children);
+            }
+        }
+        bounds
+    }
+
+    /// Entities whose bounds a ray passes through before max_toi
+    pub fn query_ray(&self, origin: Vec3, dir: Vec3, max_toi: f32) -> Vec<Entity> {
+        let mut hits = Vec::new();
+        let mut stack = self.root.into_iter().collect::<Vec<_>>();
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            if node.aabb.ray_interval(origin, dir, max_toi).is_none() {
+                continue;
+            }
+            match node.kind {
+                NodeKind::Leaf { entity, tight } => {
+                    if tight.ray_interval(origin, dir, max_toi).is_some() {
+                        hits.push(entity);
+                    }
+                }
+                NodeKind::Branch { children } => stack.extend(children),
+            }
+        }
+        hits
+    }
+
+    /// Every pair of entities with overlapping bounds, lowest entity first
+    pub fn pairs(&self) -> Vec<(Entity, Entity)> {
+        let mut pairs = Vec::new();
+        if let Some(root) = self.root {
+            self.self_pairs(root, &mut pairs);
+        }
+        pairs
+    }
+
+    // Pairs within a subtree meet at their lowest common branch
+    fn self_pairs(&self, node: usize, pairs: &mut Vec<(Entity, Entity)>) {
+        if let NodeKind::Branch { children: [a, b] } = self.nodes[node].kind {
+            self.self_pairs(a, pairs);
+            self.self_pairs(b, pairs);
+            self.cross_pairs(a, b, pairs);
+        }
+    }
+
+    fn cross_pairs(&self, a: usize, b: usize, pairs: &mut Vec<(Entity, Entity)>) {
+        let (node_a, node_b) = (&self.nodes[a], &self.nodes[b]);
+        if !node_a.aabb.intersects(&node_b.aabb) {
+            return;
+        }
+        match (&node_a.kind, &node_b.kind) {
+            (
+                NodeKind::Leaf {
+                    entity: ea,
+                    tight: ta,
+                },
+                NodeKind::Leaf {
+                    entity: eb,
+                    tight: tb,
+                },
+            ) => {
+                if ta.intersects(tb) {
+                    pairs.push(if ea < eb { (*ea, *eb) } else { (*eb, *ea) });
+                }
+            }
+            (NodeKind::Branch { children }, NodeKind::Leaf { .. }) => {
+                self.cross_pairs(children[0], b, pairs);
+                self.cross_pairs(children[1], b, pairs);
+            }
+            (NodeKind::Leaf { .. }, NodeKind::Branch { children }) => {
+                self.cross_pairs(a, children[0], pairs);
+                self.cross_pairs(a, children[1], pairs);
+            }
+            (NodeKind::Branch { children: ca }, NodeKind::Branch { children: cb }) => {
+                // descend the bigger node first to keep the overlap tests tight
+                if node_a.aabb.surface_area() >= node_b.aabb.surface_area() {
+                    self.cross_pairs(ca[0], b, pairs);
+                    self.cross_pairs(ca[1], b, pairs);
+                } else {
+                    self.cross_pairs(a, cb[0], pairs);
+                    self.cross_pairs(a, cb[1], pairs);
+                }
+            }
+        }
+    }
+
+    fn alloc(&mut self, node: Node) -> usize {
+        match self.free.pop() {
+            Some(i) => {
+                self.nodes[i] = node;
+                i
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn alloc_leaf(&mut self, entity: Entity, aabb: Aabb) -> usize {
+        self.alloc(Node {
+            aabb: aabb.grow(self.margin),
+            parent: None,
+            kind: NodeKind::Leaf {
+                entity,
+                tight: aabb,
+            },
+        })
+    }
+
+    // Walks down picking the cheapest sibling by surface area, then splices in a new branch
+    fn insert_leaf(&mut self, leaf: usize) {
+        let root = match self.root {
+            Some(root) => root,
+            None => {
+                self.nodes[leaf].parent = None;
+                self.root = Some(leaf);
+                return;
+            }
+        };
+
+        let aabb = self.nodes[leaf].aabb;
+        let mut sibling = root;
+        while let NodeKind::Branch { children } = self.nodes[sibling].kind {
+            let area = self.nodes[sibling].aabb.surface_area();
+            let combined = self.nodes[sibling].aabb.union(&aabb).surface_area();
+
+            // cost of pairing with this node, and the cost pushed onto every child below it
+            let cost = 2.0 * combined;
+            let inheritance = 2.0 * (combined - area);
+            let child_cost = |child: usize| {
+                let node = &self.nodes[child];
+                let union = node.aabb.union(&aabb).surface_area();
+                match node.kind {
+                    NodeKind::Leaf { .. } => union + inheritance,
+                    NodeKind::Branch { .. } => union - node.aabb.surface_area() + inheritance,
+                }
+            };
+            let (cost_0, cost_1) = (child_cost(children[0]), child_cost(children[1]));
+            if cost < cost_0 && cost < cost_1 {
+                break;
+            }
+            sibling = if cost_0 < cost_1 {
+                children[0]
+            } else {
+                children[1]
+            };
+        }
+
+        let old_parent = self.nodes[sibling].parent;
+        let branch = self.alloc(Node {
+            aabb: self.nodes[sibling].aabb.union(&aabb),
+            parent: old_parent,
+            kind: NodeKind::Branch {
+                children: [sibling, leaf],
+            },
+        });
+        self.nodes[sibling].parent = Some(branch);
+        self.nodes[leaf].parent = Some(branch);
+        match old_parent {
+            Some(parent) => self.replace_child(parent, sibling, branch),
+            None => self.root = Some(branch),
+        }
+        self.refit(old_parent);
+    }
+
+    // Unlinks a leaf, its sibling takes the place of their parent
+    fn remove_leaf(&mut self, leaf: usize) {
+        let parent = match self.nodes[leaf].parent {
+            Some(parent) => parent,
+            None => {
+                self.root = None;
+                return;
+            }
+        };
+        let sibling = match self.nodes[parent].kind {
+            NodeKind::Branch { children } if children[0] == leaf => children[1],
+            NodeKind::Branch { children } => children[0],
+            NodeKind::Leaf { .. } => unreachable!("leaf parent is a leaf"),
+        };
+
+        let grandparent = self.nodes[parent].parent;
+        self.nodes[sibling].parent = grandparent;
+        match grandparent {
+            Some(grandparent) => self.replace_child(grandparent, parent, sibling),
+            None => self.root = Some(sibling),
+        }
+        self.nodes[leaf].parent = None;
+        self.free.push(parent);
+        self.refit(grandparent);
+    }
+
+    fn replace_child(&mut self, parent: usize, old: usize, new: usize) {
+        if let NodeKind::Branch { children } = &mut self.nodes[parent].kind {
+            for child in children.iter_mut() {
+                if *child == old {
+                    *child = new;
+                }
+            }
+        }
+    }
+
+    // Recomputes branch bounds from a node up to the root
+    fn refit(&mut self, mut node: Option<usize>) {
+        while let Some(i) = node {
+            if let NodeKind::Branch { children: [a, b] } = self.nodes[i].kind {
+                self.nodes[i].aabb = self.nodes[a].aabb.union(&self.nodes[b].aabb);
+            }
+            node = self.nodes[i].parent;
+        }
+    }
+
+    // Top down build, splits along the widest axis at the cheapest of SAH_BINS buckets
+    fn build_node(&mut self, leaves: &mut [usize], parent: Option<usize>) -> usize {
+        if leaves.len() == 1 {
+            self.nodes[leaves[0]].parent = parent;
+            return leaves[0];
+        }
+
+        let centers = leaves
+            .iter()
+            .map(|i| self.nodes[*i].aabb.center())
+            .collect::<Vec<_>>();
+        let (min, max) = centers.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), c| (min.min(*c), max.max(*c)),
+        );
+        let extent = max - min;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z {
+            0
+        } else if extent.y >= extent.z {
+            1
+        } else {
+            2
+        };
+
+        let split = if extent[axis] > f32::EPSILON {
+            let bin_of = |c: Vec3| {
+                let t = (c[axis] - min[axis]) / extent[axis];
+                ((t * SAH_BINS as f32) as usize).min(SAH_BINS - 1)
+            };
+            let mut bins: [(usize, Option<Aabb>); SAH_BINS] = [(0, None); SAH_BINS];
+            for (leaf, center) in leaves.iter().zip(centers.iter()) {
+                let bin = &mut bins[bin_of(*center)];
+                let aabb = self.nodes[*leaf].aabb;
+                bin.0 += 1;
+                bin.1 = Some(bin.1.map_or(aabb, |b| b.union(&aabb)));
+            }
+
+            // cost of splitting before each bin, area times count on both sides
+            let side_cost = |bins: &[(usize, Option<Aabb>)]| {
+                let count: usize = bins.iter().map(|b| b.0).sum();
+                let aabb = bins.iter().filter_map(|b| b.1).reduce(|a, b| a.union(&b));
+                aabb.map_or(0.0, |a| a.surface_area() * count as f32)
+            };
+            let best = (1..SAH_BINS)
+                .map(|i| (i, side_cost(&bins[..i]) + side_cost(&bins[i..])))
+                .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                .map(|(i, _)| i)
+                .unwrap();
+
+            let mut left = 0;
+            for i in 0..leaves.len() {
+                if bin_of(self.nodes[leaves[i]].aabb.center()) < best {
+                    leaves.swap(i, left);
+                    left += 1;
+                }
+            }
+            left
+        } else {
+            0
+        };
+        // everything landed on one side, fall back to an even split
+        let split = if split == 0 || split == leaves.len() {
+            leaves.len() / 2
+        } else {
+            split
+        };
+
+        let branch = self.alloc(Node {
+            aabb: self.nodes[leaves[0]].aabb,
+            parent,
+            kind: NodeKind::Branch { children: [0, 0] },
+        });
+        let (left, right) = leaves.split_at_mut(split);
+        let a = self.build_node(left, Some(branch));
+        let b = self.build_node(right, Some(branch));
+        self.nodes[branch].aabb = self.nodes[a].aabb.union(&self.nodes[b].aabb);
+        self.nodes[branch].kind = NodeKind::Branch { children: [a, b] };
+        branch
+    }
+}
@@ -1,11 +1,24 @@
+use std::{f32::consts::PI, sync::Arc};
 
 use bevy::{prelude::*, math::vec3};
-use bevy_inspector_egui::Inspectable;
+use bevy_inspector_egui::{egui, Context, Inspectable};
 
-#[derive(Component, Inspectable, Debug)]
+use super::{Compound, CompoundPart, ConvexHull, TriMesh};
+
+#[derive(Component, Debug, Clone)]
 pub enum Collider {
     Sphere { radius: f32 },
+    /// size is the half extents of the box
     Cuboid { size: Vec3 },
+    /// Rounded cylinder along the local Y axis, half_height is to the centers of the ends
+    Capsule { half_height: f32, radius: f32 },
+    /// Everything below a plane through the origin, normal in local space. Has no mass
+    Plane { normal: Vec3 },
+    ConvexHull(Arc<ConvexHull>),
+    /// Has no mass, meant for static level geometry
+    TriMesh(Arc<TriMesh>),
+    /// Built from the colliders of a body's children, see compound_collider_system
+    Compound(Arc<Compound>),
 }
 
 impl Collider {
@@ -17,21 +30,305 @@ impl Collider {
         Collider::Cuboid { size: vec3(x, y, z) }
     }
 
+    pub fn capsule(half_height: f32, radius: f32) -> Self {
+        Collider::Capsule {
+            half_height,
+            radius,
+        }
+    }
+
+    pub fn plane(normal: Vec3) -> Self {
+        Collider::Plane {
+            normal: normal.normalize(),
+        }
+    }
+
+    /// Hull around the points, None when they are all on one plane
+    pub fn convex_hull(points: &[Vec3]) -> Option<Self> {
+        ConvexHull::new(points).map(|hull| Collider::ConvexHull(Arc::new(hull)))
+    }
+
+    pub fn convex_hull_from_mesh(mesh: &Mesh) -> Option<Self> {
+        ConvexHull::from_mesh(mesh).map(|hull| Collider::ConvexHull(Arc::new(hull)))
+    }
+
+    pub fn trimesh(vertices: Vec<Vec3>, triangles: Vec<[u32; 3]>) -> Self {
+        Collider::TriMesh(Arc::new(TriMesh::new(vertices, triangles)))
+    }
+
+    pub fn trimesh_from_mesh(mesh: &Mesh) -> Option<Self> {
+        TriMesh::from_mesh(mesh).map(|mesh| Collider::TriMesh(Arc::new(mesh)))
+    }
+
+    /// Shapes placed relative to the body, for bodies not built from child entities
+    pub fn compound(parts: Vec<(Transform, Collider)>) -> Self {
+        let parts = parts
+            .into_iter()
+            .map(|(transform, collider)| CompoundPart {
+                entity: None,
+                transform,
+                collider,
+            })
+            .collect();
+        Collider::Compound(Arc::new(Compound::new(parts)))
+    }
+
     pub fn get_center_of_mass(&self) -> Vec3 {
         match self {
             Collider::Sphere { radius } => vec3(0.0, 0.0, 0.0),
             Collider::Cuboid { size } => vec3(0.0, 0.0, 0.0),
+            Collider::ConvexHull(hull) => hull.center_of_mass(),
+            Collider::Compound(compound) => compound.center_of_mass(),
+            _ => Vec3::ZERO,
+        }
+    }
+
+    pub fn volume(&self) -> f32 {
+        match self {
+            Collider::Sphere { radius } => 4.0 / 3.0 * PI * radius * radius * radius,
+            Collider::Cuboid { size } => 8.0 * size.x * size.y * size.z,
+            Collider::Capsule {
+                half_height,
+                radius,
+            } => PI * radius * radius * (2.0 * half_height + 4.0 / 3.0 * radius),
+            Collider::ConvexHull(hull) => hull.volume(),
+            Collider::Compound(compound) => compound.volume(),
+            Collider::Plane { .. } | Collider::TriMesh(_) => 0.0,
         }
     }
 
+    /// Inertia tensor about the center of mass for a mass of 1
     pub fn get_inertia_tensor(&self) -> Mat3 {
         match self {
             Collider::Sphere { radius } => {
                 let i = 2.0 * radius * radius / 5.0;
                 Mat3::from_diagonal(Vec3::splat(i) )
             },
-            Collider::Cuboid { size } => todo!(),
+            Collider::Cuboid { size } => {
+                // (2h)^2 / 12 per axis, with h the half extents
+                let sq = *size * *size;
+                Mat3::from_diagonal(vec3(sq.y + sq.z, sq.x + sq.z, sq.x + sq.y) / 3.0)
+            },
+            Collider::Capsule { half_height, radius } => {
+                // a cylinder with half a sphere on each end
+                let (h, r2) = (2.0 * half_height, radius * radius);
+                let cylinder = PI * r2 * h;
+                let cap = 2.0 / 3.0 * PI * r2 * radius;
+                let axial = cylinder * r2 / 2.0 + 2.0 * cap * 2.0 * r2 / 5.0;
+                let across = cylinder * (h * h / 12.0 + r2 / 4.0)
+                    + 2.0 * cap * (2.0 * r2 / 5.0 + h * h / 4.0 + 3.0 * h * radius / 8.0);
+                Mat3::from_diagonal(vec3(across, axial, across) / (cylinder + 2.0 * cap))
+            },
+            Collider::ConvexHull(hull) => hull.inertia(),
+            Collider::Compound(compound) => compound.inertia(),
+            Collider::Plane { .. } | Collider::TriMesh(_) => Mat3::ZERO,
+        }
+
+    }
+}
+
+// The derive would need a default hull, mesh and compound to switch to, and those are
+// shared and built from data, so only the simple shapes can be edited
+impl Inspectable for Collider {
+    type Attributes = ();
+
+    fn ui(&mut self, ui: &mut egui::Ui, _: Self::Attributes, context: &mut Context) -> bool {
+        let mut changed = false;
+        egui::Grid::new(context.id()).show(ui, |ui| match self {
+            Collider::Sphere { radius } => {
+                changed |= shape_field(ui, "Sphere radius", radius, &mut context.with_id(0));
+            }
+            Collider::Cuboid { size } => {
+                changed |= shape_field(ui, "Cuboid half extents", size, &mut context.with_id(0));
+            }
+            Collider::Capsule {
+                half_height,
+                radius,
+            } => {
+                let mut height_context = context.with_id(0);
+                changed |= shape_field(ui, "Capsule half height", half_height, &mut height_context);
+                changed |= shape_field(ui, "Capsule radius", radius, &mut context.with_id(1));
+            }
+            Collider::Plane { normal } => {
+                if shape_field(ui, "Plane normal", normal, &mut context.with_id(0)) {
+                    *normal = normal.try_normalize().unwrap_or(Vec3::Y);
+                    changed = true;
+                }
+            }
+            Collider::ConvexHull(hull) => {
+                ui.label(format!("Convex hull of {} points", hull.points().len()));
+            }
+            Collider::TriMesh(mesh) => {
+                ui.label(format!("Triangle mesh of {} triangles", mesh.triangles().len()));
+            }
+            Collider::Compound(compound) => {
+                ui.label(format!("Compound of {} parts", compound.parts().len()));
+            }
+        });
+        changed
+    }
+}
+
+fn shape_field<T: Inspectable>(
+    ui: &mut egui::Ui,
+    label: &str,
+    value: &mut T,
+    context: &mut Context,
+) -> bool {
+    ui.label(label);
+    let changed = value.ui(ui, Default::default(), context);
+    ui.end_row();
+    changed
+}
+
+/// Builds a collider from the entity's mesh once the mesh has loaded
+#[derive(Component, Debug, Clone, Copy)]
+pub enum MeshCollider {
+    ConvexHull,
+    TriMesh,
+}
+
+// Swaps MeshCollider for the collider it describes as soon as the mesh is available
+pub fn mesh_collider_system(
+    mut commands: Commands,
+    meshes: Option<Res<Assets<Mesh>>>,
+    query: Query<(Entity, &MeshCollider, &Handle<Mesh>)>,
+) {
+    let meshes = match meshes {
+        Some(meshes) => meshes,
+        None => return,
+    };
+    for (e, kind, handle) in query.iter() {
+        let mesh = match meshes.get(handle) {
+            Some(mesh) => mesh,
+            None => continue,
+        };
+        let collider = match kind {
+            MeshCollider::ConvexHull => Collider::convex_hull_from_mesh(mesh),
+            MeshCollider::TriMesh => Collider::trimesh_from_mesh(mesh),
+        };
+        match collider {
+            Some(collider) => {
+                commands.entity(e).insert(collider);
+            }
+            None => warn!("Can't build a {:?} collider from the mesh of {:?}", kind, e),
+        }
+        commands.entity(e).remove::<MeshCollider>();
+    }
+}
+
+/// Which groups a collider belongs to and which it collides with. Two colliders only
+/// interact when each is a member of a group the other's filter accepts
+#[derive(Component, Inspectable, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CollisionGroups {
+    pub memberships: u32,
+    pub filters: u32,
+}
+
+impl CollisionGroups {
+    pub const ALL: u32 = u32::MAX;
+    pub const NONE: u32 = 0;
+
+    pub fn new(memberships: u32, filters: u32) -> Self {
+        Self {
+            memberships,
+            filters,
+        }
+    }
+
+    pub fn interacts_with(&self, other: &CollisionGroups) -> bool {
+        self.memberships & other.filters != 0 && other.memberships & self.filters != 0
+    }
+}
+
+impl Default for CollisionGroups {
+    fn default() -> Self {
+        Self::new(Self::ALL, Self::ALL)
+    }
+}
+
+/// Trigger volume, overlaps are reported as collision events but nothing is pushed apart
+#[derive(Component, Inspectable, Debug, Default, Clone, Copy)]
+pub struct Sensor;
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::event::Events;
+
+    use super::*;
+    use crate::physics::{
+        tests::{app, step},
+        CollisionEnded, CollisionStarted, Gravity, LinearVelocity, Static,
+    };
+
+    #[test]
+    fn groups_filter_both_ways() {
+        const PLAYER: u32 = 0b001;
+        const ENEMY: u32 = 0b010;
+        const PICKUP: u32 = 0b100;
+        let player = CollisionGroups::new(PLAYER, ENEMY | PICKUP);
+        let enemy = CollisionGroups::new(ENEMY, PLAYER);
+        let pickup = CollisionGroups::new(PICKUP, PLAYER);
+
+        assert!(player.interacts_with(&enemy));
+        assert!(enemy.interacts_with(&player));
+        assert!(player.interacts_with(&pickup));
+        // each accepts the other's membership or neither does
+        assert!(!enemy.interacts_with(&pickup));
+        assert!(!pickup.interacts_with(&enemy));
+
+        // player wants ghosts but ghosts don't want the player, so they pass through
+        let ghost = CollisionGroups::new(ENEMY, CollisionGroups::NONE);
+        assert!(!player.interacts_with(&ghost));
+        assert!(!ghost.interacts_with(&player));
+
+        let default = CollisionGroups::default();
+        assert!(default.interacts_with(&player));
+        assert!(player.interacts_with(&default));
+        let none = CollisionGroups::new(CollisionGroups::NONE, CollisionGroups::ALL);
+        assert!(!none.interacts_with(&default));
+        assert!(!default.interacts_with(&none));
+    }
+
+    #[test]
+    fn sensor_reports_without_pushing() {
+        let mut app = app();
+        app.insert_resource(Gravity(Vec3::ZERO));
+        let sensor = app
+            .world
+            .spawn()
+            .insert(Transform::default())
+            .insert(Collider::cuboid(1.0, 1.0, 1.0))
+            .insert(Static)
+            .insert(Sensor)
+            .id();
+        let launch = -Vec3::Y * 6.0;
+        let ball = app
+            .world
+            .spawn()
+            .insert(Transform::from_xyz(0.0, 2.0, 0.0))
+            .insert(Collider::sphere(0.5))
+            .insert(LinearVelocity(launch))
+            .id();
+
+        let mut started_reader = app.world.resource::<Events<CollisionStarted>>().get_reader();
+        let mut ended_reader = app.world.resource::<Events<CollisionEnded>>().get_reader();
+        let (mut started, mut ended) = (Vec::new(), Vec::new());
+        // long enough to pass right through the volume
+        for _ in 0..60 {
+            step(&mut app);
+            let events = app.world.resource::<Events<CollisionStarted>>();
+            started.extend(started_reader.iter(events).cloned());
+            let events = app.world.resource::<Events<CollisionEnded>>();
+            ended.extend(ended_reader.iter(events).cloned());
+            assert_eq!(app.world.get::<LinearVelocity>(ball).unwrap().0, launch);
         }
 
+        let pair = if ball < sensor { (ball, sensor) } else { (sensor, ball) };
+        assert_eq!(started.len(), 1);
+        assert_eq!((started[0].0, started[0].1), pair);
+        assert_eq!(ended.len(), 1);
+        assert_eq!((ended[0].0, ended[0].1), pair);
+        assert!(app.world.get::<Transform>(ball).unwrap().translation.y < -1.5);
     }
 }
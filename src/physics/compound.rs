@@ -0,0 +1,304 @@
+use std::sync::Arc;
+
+use bevy::{prelude::*, utils::HashSet};
+
+use super::{Collider, RigidBody};
+
+/// Several colliders moving as one body, each placed relative to the body
+#[derive(Debug, Clone)]
+pub struct Compound {
+    parts: Vec<CompoundPart>,
+    volume: f32,
+    center_of_mass: Vec3,
+    // about the center of mass, for a mass of 1
+    inertia: Mat3,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompoundPart {
+    /// The child entity the part came from, contacts with the part are attributed to it
+    pub entity: Option<Entity>,
+    /// Relative to the body, scale is ignored
+    pub transform: Transform,
+    pub collider: Collider,
+}
+
+impl Compound {
+    pub fn new(parts: Vec<CompoundPart>) -> Self {
+        // every part has the body's density, so parts are weighted by volume
+        let volume = parts.iter().map(|p| p.collider.volume()).sum::<f32>();
+        if volume <= f32::EPSILON {
+            return Self {
+                parts,
+                volume: 0.0,
+                center_of_mass: Vec3::ZERO,
+                inertia: Mat3::ZERO,
+            };
+        }
+        let part_center = |p: &CompoundPart| {
+            p.transform.translation + p.transform.rotation * p.collider.get_center_of_mass()
+        };
+        let center_of_mass = parts
+            .iter()
+            .fold(Vec3::ZERO, |sum, p| sum + part_center(p) * p.collider.volume())
+            / volume;
+
+        // each part's tensor turned into the body's frame and moved to the shared center
+        let inertia = parts.iter().fold(Mat3::ZERO, |sum, p| {
+            let rot = Mat3::from_quat(p.transform.rotation);
+            let d = part_center(p) - center_of_mass;
+            let offset = Mat3::from_diagonal(Vec3::splat(d.dot(d)))
+                - Mat3::from_cols(d * d.x, d * d.y, d * d.z);
+            let local = rot * p.collider.get_inertia_tensor() * rot.transpose();
+            sum + (local + offset) * (p.collider.volume() / volume)
+        });
+
+        Self {
+            parts,
+            volume,
+            center_of_mass,
+            inertia,
+        }
+    }
+
+    pub fn parts(&self) -> &[CompoundPart] {
+        &self.parts
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub fn center_of_mass(&self) -> Vec3 {
+        self.center_of_mass
+    }
+
+    /// Inertia tensor about the center of mass for a mass of 1
+    pub fn inertia(&self) -> Mat3 {
+        self.inertia
+    }
+}
+
+impl CompoundPart {
+    /// Where the part is in the world for a body at trans
+    pub fn world_transform(&self, trans: &Transform) -> Transform {
+        Transform {
+            translation: trans.translation + trans.rotation * self.transform.translation,
+            rotation: trans.rotation * self.transform.rotation,
+            scale: Vec3::ONE,
+        }
+    }
+}
+
+impl Collider {
+    /// The shapes making up the collider placed in the world with the entity they came
+    /// from, just the collider itself unless it is a compound
+    pub fn parts<'a>(
+        &'a self,
+        trans: &'a Transform,
+    ) -> impl Iterator<Item = (Option<Entity>, &'a Collider, Transform)> + 'a {
+        let (single, compound) = match self {
+            Collider::Compound(compound) => (None, Some(compound.parts.iter())),
+            _ => (Some((None, self, *trans)), None),
+        };
+        single.into_iter().chain(
+            compound
+                .into_iter()
+                .flatten()
+                .map(move |p| (p.entity, &p.collider, p.world_transform(trans))),
+        )
+    }
+}
+
+/// Lets systems walk up from a collider to the body it belongs to
+pub type BodyHierarchy<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Option<&'static Parent>,
+        &'static Transform,
+        Option<&'static RigidBody>,
+    ),
+>;
+
+/// The nearest ancestor with a RigidBody, and the entity's transform relative to it.
+/// A collider with one is a part of that body's compound collider, not a body itself
+pub fn owning_body(entity: Entity, hierarchy: &BodyHierarchy) -> Option<(Entity, Transform)> {
+    let mut local = Transform::identity();
+    let mut current = entity;
+    loop {
+        let (parent, trans, _) = hierarchy.get(current).ok()?;
+        local = trans.mul_transform(local);
+        let parent = parent?.0;
+        if let (_, _, Some(_)) = hierarchy.get(parent).ok()? {
+            return Some((parent, local));
+        }
+        current = parent;
+    }
+}
+
+// Gathers the colliders under each rigid body into a compound collider on the body, rebuilt
+// whenever a part is added, changed, moved or removed. The body's own collider is replaced,
+// so its shapes all go on children
+pub fn compound_collider_system(
+    mut commands: Commands,
+    changed: Query<
+        Entity,
+        (
+            With<Collider>,
+            With<Parent>,
+            Or<(Changed<Collider>, Changed<Transform>, Changed<Parent>)>,
+        ),
+    >,
+    removed: RemovedComponents<Collider>,
+    hierarchy: BodyHierarchy,
+    children: Query<&Children>,
+    colliders: Query<(Entity, &Collider)>,
+) {
+    let mut dirty = changed
+        .iter()
+        .filter_map(|e| owning_body(e, &hierarchy).map(|(body, _)| body))
+        .collect::<HashSet<_>>();
+    let removed = removed.iter().collect::<HashSet<_>>();
+    if !removed.is_empty() {
+        let was_removed = |p: &CompoundPart| p.entity.map_or(false, |e| removed.contains(&e));
+        for (e, collider) in colliders.iter() {
+            if let Collider::Compound(compound) = collider {
+                if compound.parts.iter().any(was_removed) {
+                    dirty.insert(e);
+                }
+            }
+        }
+    }
+
+    for body in dirty {
+        // every collider below the body down to the next body, which is a part of its own
+        let mut parts = Vec::new();
+        let mut stack = children.get(body).map_or(Vec::new(), |c| c.to_vec());
+        while let Some(e) = stack.pop() {
+            let (_, _, nested_body) = match hierarchy.get(e) {
+                Ok(node) => node,
+                Err(_) => continue,
+            };
+            if let (Ok((_, collider)), Some((_, transform))) =
+                (colliders.get(e), owning_body(e, &hierarchy))
+            {
+                parts.push(CompoundPart {
+                    entity: Some(e),
+                    transform,
+                    collider: collider.clone(),
+                });
+            }
+            if nested_body.is_none() {
+                stack.extend(children.get(e).map_or(&[][..], |c| &c[..]));
+            }
+        }
+
+        match colliders.get(body) {
+            Ok((_, Collider::Compound(_))) | Err(_) => {}
+            Ok(_) => warn!("The collider of {:?} is replaced by its children's colliders", body),
+        }
+        if parts.is_empty() {
+            commands.entity(body).remove::<Collider>();
+        } else {
+            commands
+                .entity(body)
+                .insert(Collider::Compound(Arc::new(Compound::new(parts))));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::{
+        tests::{app, step},
+        CenterOfMass, Contacts, Density, InertiaTensor, Mass, Static,
+    };
+
+    const EPS: f32 = 1e-4;
+
+    fn cube_part(x: f32) -> CompoundPart {
+        CompoundPart {
+            entity: None,
+            transform: Transform::from_xyz(x, 0.0, 0.0),
+            collider: Collider::cuboid(0.5, 0.5, 0.5),
+        }
+    }
+
+    // Two unit cubes 2 apart along x, for a mass of 1 each cube is 1/6 about its own center
+    // and the offset of 1 from the shared center adds 1 about y and z
+    fn expected_inertia() -> Mat3 {
+        Mat3::from_diagonal(Vec3::new(1.0 / 6.0, 7.0 / 6.0, 7.0 / 6.0))
+    }
+
+    #[test]
+    fn offset_parts_move_the_center_of_mass() {
+        let compound = Compound::new(vec![cube_part(0.0), cube_part(2.0)]);
+        assert!((compound.volume() - 2.0).abs() < EPS);
+        assert!(compound.center_of_mass().abs_diff_eq(Vec3::X, EPS));
+        assert!(compound.inertia().abs_diff_eq(expected_inertia(), EPS));
+
+        // turning a part changes nothing for a cube
+        let mut turned = cube_part(2.0);
+        turned.transform.rotation = Quat::from_rotation_y(0.7);
+        let compound = Compound::new(vec![cube_part(0.0), turned]);
+        assert!(compound.inertia().abs_diff_eq(expected_inertia(), EPS));
+    }
+
+    #[test]
+    fn child_colliders_make_one_body() {
+        let mut app = app();
+        // static ground with its top at y = 0
+        app.world
+            .spawn()
+            .insert(Transform::from_xyz(0.0, -0.5, 0.0))
+            .insert(Collider::cuboid(50.0, 0.5, 50.0))
+            .insert(Static);
+        let mut parts = Vec::new();
+        let body = app
+            .world
+            .spawn()
+            .insert(Transform::from_xyz(0.0, 0.55, 0.0))
+            .insert(RigidBody)
+            .insert(Density(1.0))
+            .with_children(|body| {
+                for x in [0.0, 2.0] {
+                    let part = body
+                        .spawn()
+                        .insert(Transform::from_xyz(x, 0.0, 0.0))
+                        .insert(Collider::cuboid(0.5, 0.5, 0.5))
+                        .id();
+                    parts.push(part);
+                }
+            })
+            .id();
+
+        let mut touched = HashSet::default();
+        for _ in 0..30 {
+            step(&mut app);
+            for contact in app.world.resource::<Contacts>().0.iter() {
+                // contacts are between bodies, the part that touched is kept alongside
+                let (entity, collider) = if contact.entity_a == body {
+                    (contact.entity_a, contact.collider_a)
+                } else {
+                    (contact.entity_b, contact.collider_b)
+                };
+                assert_eq!(entity, body);
+                assert!(!parts.contains(&contact.entity_a));
+                assert!(!parts.contains(&contact.entity_b));
+                touched.insert(collider);
+            }
+        }
+        assert_eq!(touched, parts.iter().copied().collect());
+
+        assert!((app.world.get::<Mass>(body).unwrap().0 - 2.0).abs() < EPS);
+        let center = app.world.get::<CenterOfMass>(body).unwrap().0;
+        assert!(center.abs_diff_eq(Vec3::X, EPS), "center of mass at {}", center);
+        let inertia = app.world.get::<InertiaTensor>(body).unwrap().0;
+        assert!(inertia.abs_diff_eq(expected_inertia() * 2.0, EPS));
+        // resting on both cubes it stays level
+        let trans = app.world.get::<Transform>(body).unwrap();
+        assert!(trans.rotation.angle_between(Quat::IDENTITY) < 0.01);
+    }
+}
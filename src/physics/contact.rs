@@ -0,0 +1,1105 @@
+use bevy::prelude::*;
+
+use super::{Collider, ConvexHull, HullEdge, TriMesh};
+
+// Box corners are numbered by bits, x in the lowest. Faces are +x -x +y -y +z -z, wound
+// counter clockwise from outside
+static BOX_FACES: [[usize; 4]; 6] = [
+    [1, 3, 7, 5],
+    [0, 4, 6, 2],
+    [2, 6, 7, 3],
+    [0, 1, 5, 4],
+    [4, 5, 7, 6],
+    [0, 2, 3, 1],
+];
+
+#[rustfmt::skip]
+static BOX_EDGES: [HullEdge; 12] = [
+    HullEdge { a: 0, b: 1, faces: [3, 5] },
+    HullEdge { a: 2, b: 3, faces: [2, 5] },
+    HullEdge { a: 4, b: 5, faces: [3, 4] },
+    HullEdge { a: 6, b: 7, faces: [2, 4] },
+    HullEdge { a: 0, b: 2, faces: [1, 5] },
+    HullEdge { a: 1, b: 3, faces: [0, 5] },
+    HullEdge { a: 4, b: 6, faces: [1, 4] },
+    HullEdge { a: 5, b: 7, faces: [0, 4] },
+    HullEdge { a: 0, b: 4, faces: [1, 3] },
+    HullEdge { a: 1, b: 5, faces: [0, 3] },
+    HullEdge { a: 2, b: 6, faces: [1, 2] },
+    HullEdge { a: 3, b: 7, faces: [0, 2] },
+];
+
+// Triangles are two sided, front then back
+static TRIANGLE_FACES: [[usize; 3]; 2] = [[0, 1, 2], [0, 2, 1]];
+
+#[rustfmt::skip]
+static TRIANGLE_EDGES: [HullEdge; 3] = [
+    HullEdge { a: 0, b: 1, faces: [0, 1] },
+    HullEdge { a: 1, b: 2, faces: [0, 1] },
+    HullEdge { a: 2, b: 0, faces: [0, 1] },
+];
+
+// Contacts from triangles of a mesh facing this close to the deepest one are kept with it
+const TRIMESH_MERGE_DOT: f32 = 0.9;
+
+/// A single point of contact, both points are in world space
+#[derive(Debug, Clone, Copy)]
+pub struct ContactPoint {
+    /// Deepest point of b inside a, on the surface of a
+    pub point_a: Vec3,
+    /// Deepest point of a inside b, on the surface of b
+    pub point_b: Vec3,
+    /// Penetration along the normal, positive when overlapping
+    pub depth: f32,
+}
+
+/// Contact points between two shapes sharing one normal
+#[derive(Debug, Clone)]
+pub struct ContactManifold {
+    /// World space normal, pointing from a to b
+    pub normal: Vec3,
+    pub points: Vec<ContactPoint>,
+}
+
+impl ContactManifold {
+    pub fn max_depth(&self) -> f32 {
+        self.points.iter().fold(0.0, |max, p| max.max(p.depth))
+    }
+
+    // Same contact seen from the other shape
+    fn flipped(mut self) -> Self {
+        self.normal = -self.normal;
+        for p in self.points.iter_mut() {
+            std::mem::swap(&mut p.point_a, &mut p.point_b);
+        }
+        self
+    }
+}
+
+/// A pair of colliding entities found by the narrow phase
+#[derive(Debug, Clone)]
+pub struct Contact {
+    /// The bodies in contact
+    pub entity_a: Entity,
+    pub entity_b: Entity,
+    /// The colliders that touched, the part of a compound body or else the body itself
+    pub collider_a: Entity,
+    pub collider_b: Entity,
+    pub manifold: ContactManifold,
+    /// One side is a sensor, the contact is only reported and never resolved
+    pub sensor: bool,
+}
+
+/// Contacts found this frame, rebuilt every frame by the narrow phase
+#[derive(Debug, Default, Clone)]
+pub struct Contacts(pub Vec<Contact>);
+
+impl Collider {
+    /// Contact between two colliders placed by their transforms, None if they are apart.
+    /// Colliders are centered on the transform and ignore its scale
+    pub fn contact(
+        &self,
+        trans: &Transform,
+        other: &Collider,
+        other_trans: &Transform,
+    ) -> Option<ContactManifold> {
+        match (self, other) {
+            // the deepest of the contacts between the parts
+            (Collider::Compound(_), _) | (_, Collider::Compound(_)) => self
+                .parts(trans)
+                .flat_map(|(_, a, trans_a)| {
+                    other
+                        .parts(other_trans)
+                        .filter_map(move |(_, b, trans_b)| a.contact(&trans_a, b, &trans_b))
+                })
+                .max_by(|a, b| {
+                    a.max_depth()
+                        .partial_cmp(&b.max_depth())
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                }),
+            (Collider::Sphere { radius: ra }, Collider::Sphere { radius: rb }) => {
+                sphere_sphere(trans.translation, *ra, other_trans.translation, *rb)
+            }
+            (Collider::Sphere { radius }, Collider::Cuboid { size }) => {
+                sphere_cuboid(trans.translation, *radius, &Obb::new(other_trans, *size))
+            }
+            (Collider::Cuboid { size }, Collider::Sphere { radius }) => {
+                sphere_cuboid(other_trans.translation, *radius, &Obb::new(trans, *size))
+                    .map(ContactManifold::flipped)
+            }
+            (Collider::Cuboid { size: sa }, Collider::Cuboid { size: sb }) => {
+                cuboid_cuboid(&Obb::new(trans, *sa), &Obb::new(other_trans, *sb))
+            }
+            (Collider::Plane { normal }, _) => plane_contact(trans, *normal, other, other_trans),
+            (_, Collider::Plane { normal }) => {
+                plane_contact(other_trans, *normal, self, trans).map(ContactManifold::flipped)
+            }
+            (Collider::TriMesh(mesh), _) => trimesh_contact(mesh, trans, other, other_trans),
+            (_, Collider::TriMesh(mesh)) => {
+                trimesh_contact(mesh, other_trans, self, trans).map(ContactManifold::flipped)
+            }
+            _ => match (Convex::new(self, trans)?, Convex::new(other, other_trans)?) {
+                (Convex::Rounded(a), Convex::Rounded(b)) => {
+                    let (pa, pb) = closest_segment_points(a.start, a.end, b.start, b.end);
+                    sphere_sphere(pa, a.radius, pb, b.radius)
+                }
+                (Convex::Rounded(a), Convex::Solid(b)) => rounded_poly(&a, &b),
+                (Convex::Solid(a), Convex::Rounded(b)) => {
+                    rounded_poly(&b, &a).map(ContactManifold::flipped)
+                }
+                (Convex::Solid(a), Convex::Solid(b)) => poly_poly(&a, &b),
+            },
+        }
+    }
+}
+
+// Oriented box in world space
+struct Obb {
+    center: Vec3,
+    axes: [Vec3; 3],
+    half: Vec3,
+}
+
+impl Obb {
+    fn new(trans: &Transform, half: Vec3) -> Self {
+        let rot = Mat3::from_quat(trans.rotation);
+        Self {
+            center: trans.translation,
+            axes: [rot.x_axis, rot.y_axis, rot.z_axis],
+            half,
+        }
+    }
+
+    // Half length of the box projected on an axis
+    fn project(&self, axis: Vec3) -> f32 {
+        (0..3)
+            .map(|i| self.half[i] * self.axes[i].dot(axis).abs())
+            .sum()
+    }
+
+    fn to_local(&self, p: Vec3) -> Vec3 {
+        let d = p - self.center;
+        Vec3::new(d.dot(self.axes[0]), d.dot(self.axes[1]), d.dot(self.axes[2]))
+    }
+
+    fn to_world(&self, p: Vec3) -> Vec3 {
+        self.center + self.axes[0] * p.x + self.axes[1] * p.y + self.axes[2] * p.z
+    }
+}
+
+fn sphere_sphere(ca: Vec3, ra: f32, cb: Vec3, rb: f32) -> Option<ContactManifold> {
+    let d = cb - ca;
+    let dist = d.length();
+    if dist > ra + rb {
+        return None;
+    }
+
+    // concentric spheres have no good normal, push them apart vertically
+    let normal = if dist > f32::EPSILON { d / dist } else { Vec3::Y };
+    Some(ContactManifold {
+        normal,
+        points: vec![ContactPoint {
+            point_a: ca + normal * ra,
+            point_b: cb - normal * rb,
+            depth: ra + rb - dist,
+        }],
+    })
+}
+
+// Normal points from the sphere to the box
+fn sphere_cuboid(center: Vec3, radius: f32, obb: &Obb) -> Option<ContactManifold> {
+    let local = obb.to_local(center);
+    let closest = local.clamp(-obb.half, obb.half);
+
+    let (normal_local, surface, depth) = if closest != local {
+        // sphere center outside the box
+        let d = local - closest;
+        let dist = d.length();
+        if dist > radius {
+            return None;
+        }
+        (d / dist, closest, radius - dist)
+    } else {
+        // center inside, push out through the nearest face
+        let gap = obb.half - local.abs();
+        let axis = if gap.x <= gap.y && gap.x <= gap.z {
+            0
+        } else if gap.y <= gap.z {
+            1
+        } else {
+            2
+        };
+        let sign = if local[axis] < 0.0 { -1.0 } else { 1.0 };
+        let mut normal = Vec3::ZERO;
+        normal[axis] = sign;
+        let mut surface = local;
+        surface[axis] = sign * obb.half[axis];
+        (normal, surface, radius + gap[axis])
+    };
+
+    // normal_local points out of the box toward the sphere
+    let out = obb.to_world(normal_local) - obb.center;
+    Some(ContactManifold {
+        normal: -out,
+        points: vec![ContactPoint {
+            point_a: center - out * radius,
+            point_b: obb.to_world(surface),
+            depth,
+        }],
+    })
+}
+
+// Separating axis test over face and edge axes, face contacts are clipped into a manifold
+fn cuboid_cuboid(a: &Obb, b: &Obb) -> Option<ContactManifold> {
+    let d = b.center - a.center;
+
+    // best face axis, 0..3 on a and 3..6 on b
+    let mut face: Option<(usize, f32, Vec3)> = None;
+    for i in 0..6 {
+        let axis = if i < 3 { a.axes[i] } else { b.axes[i - 3] };
+        let dist = d.dot(axis);
+        let overlap = a.project(axis) + b.project(axis) - dist.abs();
+        if overlap < 0.0 {
+            return None;
+        }
+        if face.map_or(true, |(_, best, _)| overlap < best) {
+            let normal = if dist < 0.0 { -axis } else { axis };
+            face = Some((i, overlap, normal));
+        }
+    }
+    let (face_axis, face_overlap, face_normal) = face.unwrap();
+
+    let mut edge: Option<(usize, usize, f32, Vec3)> = None;
+    for i in 0..3 {
+        for j in 0..3 {
+            let axis = a.axes[i].cross(b.axes[j]);
+            let len = axis.length();
+            // parallel edges are already covered by the face axes
+            if len < 1e-4 {
+                continue;
+            }
+            let axis = axis / len;
+            let dist = d.dot(axis);
+            let overlap = a.project(axis) + b.project(axis) - dist.abs();
+            if overlap < 0.0 {
+                return None;
+            }
+            if edge.map_or(true, |(_, _, best, _)| overlap < best) {
+                let normal = if dist < 0.0 { -axis } else { axis };
+                edge = Some((i, j, overlap, normal));
+            }
+        }
+    }
+
+    // prefer faces, edge contacts only win when clearly shallower
+    match edge {
+        Some((i, j, overlap, normal)) if overlap < face_overlap * 0.95 - 1e-3 => {
+            Some(edge_contact(a, b, i, j, normal, overlap))
+        }
+        _ if face_axis < 3 => Some(face_contact(a, b, face_axis, face_normal)),
+        _ => Some(face_contact(b, a, face_axis - 3, -face_normal).flipped()),
+    }
+}
+
+// Clips the incident face of b against the reference face of a
+fn face_contact(reference: &Obb, incident: &Obb, axis: usize, normal: Vec3) -> ContactManifold {
+    let ref_center = reference.center + normal * reference.half[axis];
+
+    // incident face is the one facing most against the normal
+    let (inc_axis, inc_dot) = (0..3)
+        .map(|i| (i, incident.axes[i].dot(normal)))
+        .fold((0, 0.0), |best: (usize, f32), cur| {
+            if cur.1.abs() > best.1.abs() {
+                cur
+            } else {
+                best
+            }
+        });
+    let inc_normal = incident.axes[inc_axis] * -inc_dot.signum();
+    let inc_center = incident.center + inc_normal * incident.half[inc_axis];
+    let (k, l) = ((inc_axis + 1) % 3, (inc_axis + 2) % 3);
+    let u = incident.axes[k] * incident.half[k];
+    let v = incident.axes[l] * incident.half[l];
+    let mut polygon = vec![
+        inc_center + u + v,
+        inc_center - u + v,
+        inc_center - u - v,
+        inc_center + u - v,
+    ];
+
+    // clip against the four side planes of the reference face
+    for side in [(axis + 1) % 3, (axis + 2) % 3] {
+        let side_axis = reference.axes[side];
+        let offset = side_axis.dot(reference.center);
+        for (plane, limit) in [
+            (side_axis, offset + reference.half[side]),
+            (-side_axis, -offset + reference.half[side]),
+        ] {
+            polygon = clip_polygon(&polygon, plane, limit);
+            if polygon.is_empty() {
+                break;
+            }
+        }
+    }
+
+    let points = polygon
+        .into_iter()
+        .filter_map(|p| {
+            let separation = normal.dot(p - ref_center);
+            (separation <= 0.0).then(|| ContactPoint {
+                point_a: p - normal * separation,
+                point_b: p,
+                depth: -separation,
+            })
+        })
+        .collect();
+
+    ContactManifold { normal, points }
+}
+
+// Sutherland-Hodgman, keeps the part of the polygon where plane . p <= limit
+fn clip_polygon(polygon: &[Vec3], plane: Vec3, limit: f32) -> Vec<Vec3> {
+    let mut out = Vec::with_capacity(polygon.len() + 1);
+    for (i, &start) in polygon.iter().enumerate() {
+        let end = polygon[(i + 1) % polygon.len()];
+        let ds = plane.dot(start) - limit;
+        let de = plane.dot(end) - limit;
+        if ds <= 0.0 {
+            out.push(start);
+        }
+        if (ds <= 0.0) != (de <= 0.0) {
+            out.push(start + (end - start) * (ds / (ds - de)));
+        }
+    }
+    out
+}
+
+// Closest points between the two edges that touch along the separating axis
+fn edge_contact(
+    a: &Obb,
+    b: &Obb,
+    i: usize,
+    j: usize,
+    normal: Vec3,
+    depth: f32,
+) -> ContactManifold {
+    // pick the edge of a furthest along the normal, and of b furthest against it
+    let support = |obb: &Obb, skip: usize, dir: Vec3| {
+        let mut p = obb.center;
+        for k in (0..3).filter(|k| *k != skip) {
+            let sign = if obb.axes[k].dot(dir) < 0.0 { -1.0 } else { 1.0 };
+            p += obb.axes[k] * obb.half[k] * sign;
+        }
+        p
+    };
+    let pa = support(a, i, normal);
+    let pb = support(b, j, -normal);
+    let (da, db) = (a.axes[i], b.axes[j]);
+
+    // closest points between the two edge lines, clamped to the edge lengths
+    let r = pa - pb;
+    let c = da.dot(db);
+    let denom = 1.0 - c * c;
+    let (e, f) = (da.dot(r), db.dot(r));
+    let s = if denom > 1e-6 {
+        ((c * f - e) / denom).clamp(-a.half[i], a.half[i])
+    } else {
+        0.0
+    };
+    let t = (c * s + f).clamp(-b.half[j], b.half[j]);
+
+    ContactManifold {
+        normal,
+        points: vec![ContactPoint {
+            point_a: pa + da * s,
+            point_b: pb + db * t,
+            depth,
+        }],
+    }
+}
+
+// Points within radius of a segment, spheres have a segment of zero length
+struct Rounded {
+    start: Vec3,
+    end: Vec3,
+    radius: f32,
+}
+
+// Convex polyhedron in world space, the faces and edges are borrowed from a hull or the
+// box and triangle tables
+struct Poly<'a> {
+    vertices: Vec<Vec3>,
+    faces: Vec<PolyFace<'a>>,
+    edges: &'a [HullEdge],
+    // triangles are flat, their edges can't be ruled out by where their faces point
+    solid: bool,
+}
+
+struct PolyFace<'a> {
+    normal: Vec3,
+    offset: f32,
+    vertices: &'a [usize],
+}
+
+// Convex shapes as the contact tests see them
+enum Convex<'a> {
+    Rounded(Rounded),
+    Solid(Poly<'a>),
+}
+
+impl<'a> Convex<'a> {
+    // None for planes and meshes, and compounds which are split into parts before this
+    fn new(collider: &'a Collider, trans: &Transform) -> Option<Self> {
+        let center = trans.translation;
+        match collider {
+            Collider::Sphere { radius } => Some(Convex::Rounded(Rounded {
+                start: center,
+                end: center,
+                radius: *radius,
+            })),
+            Collider::Capsule {
+                half_height,
+                radius,
+            } => {
+                let up = trans.rotation * Vec3::Y * *half_height;
+                Some(Convex::Rounded(Rounded {
+                    start: center - up,
+                    end: center + up,
+                    radius: *radius,
+                }))
+            }
+            Collider::Cuboid { size } => Some(Convex::Solid(Poly::cuboid(&Obb::new(trans, *size)))),
+            Collider::ConvexHull(hull) => Some(Convex::Solid(Poly::hull(hull, trans))),
+            Collider::Plane { .. } | Collider::TriMesh(_) | Collider::Compound(_) => None,
+        }
+    }
+}
+
+impl<'a> Poly<'a> {
+    fn cuboid(obb: &Obb) -> Poly<'static> {
+        let vertices = (0..8)
+            .map(|i| {
+                let sign = |bit: usize| if i & bit != 0 { 1.0 } else { -1.0 };
+                obb.to_world(Vec3::new(sign(1), sign(2), sign(4)) * obb.half)
+            })
+            .collect();
+        let faces = BOX_FACES
+            .iter()
+            .enumerate()
+            .map(|(i, corners)| {
+                let axis = obb.axes[i / 2];
+                let normal = if i % 2 == 0 { axis } else { -axis };
+                PolyFace {
+                    normal,
+                    offset: normal.dot(obb.center) + obb.half[i / 2],
+                    vertices: corners,
+                }
+            })
+            .collect();
+        Poly {
+            vertices,
+            faces,
+            edges: &BOX_EDGES,
+            solid: true,
+        }
+    }
+
+    fn hull(hull: &'a ConvexHull, trans: &Transform) -> Self {
+        let vertices = hull
+            .points()
+            .iter()
+            .map(|p| trans.translation + trans.rotation * *p)
+            .collect();
+        let faces = hull
+            .faces()
+            .iter()
+            .map(|face| {
+                let normal = trans.rotation * face.normal;
+                PolyFace {
+                    normal,
+                    offset: face.offset + normal.dot(trans.translation),
+                    vertices: &face.vertices,
+                }
+            })
+            .collect();
+        Poly {
+            vertices,
+            faces,
+            edges: hull.edges(),
+            solid: true,
+        }
+    }
+
+    // None for triangles with no area
+    fn triangle(corners: [Vec3; 3]) -> Option<Poly<'static>> {
+        let [a, b, c] = corners;
+        let normal = (b - a).cross(c - a).try_normalize()?;
+        let faces = vec![
+            PolyFace {
+                normal,
+                offset: normal.dot(a),
+                vertices: &TRIANGLE_FACES[0],
+            },
+            PolyFace {
+                normal: -normal,
+                offset: -normal.dot(a),
+                vertices: &TRIANGLE_FACES[1],
+            },
+        ];
+        Some(Poly {
+            vertices: corners.to_vec(),
+            faces,
+            edges: &TRIANGLE_EDGES,
+            solid: false,
+        })
+    }
+
+    fn project(&self, axis: Vec3) -> (f32, f32) {
+        self.vertices.iter().fold((f32::MAX, f32::MIN), |(min, max), v| {
+            let d = axis.dot(*v);
+            (min.min(d), max.max(d))
+        })
+    }
+
+    fn edge(&self, edge: &HullEdge) -> (Vec3, Vec3) {
+        (self.vertices[edge.a], self.vertices[edge.b])
+    }
+
+    // Closest point on the surface to a point outside
+    fn closest_point(&self, point: Vec3) -> Vec3 {
+        let mut closest = (point, f32::MAX);
+        for face in self.faces.iter() {
+            let height = face.normal.dot(point) - face.offset;
+            if height <= 0.0 {
+                continue;
+            }
+            let on_plane = point - face.normal * height;
+            let sides = face.vertices.iter().enumerate().map(|(k, i)| {
+                let next = face.vertices[(k + 1) % face.vertices.len()];
+                (self.vertices[*i], self.vertices[next])
+            });
+            let on_face = if sides
+                .clone()
+                .all(|(start, end)| (end - start).cross(face.normal).dot(on_plane - start) <= 0.0)
+            {
+                on_plane
+            } else {
+                sides
+                    .map(|(start, end)| closest_segment_points(point, point, start, end).1)
+                    .min_by(|a, b| {
+                        let (da, db) = (a.distance_squared(point), b.distance_squared(point));
+                        da.partial_cmp(&db).unwrap_or(std::cmp::Ordering::Equal)
+                    })
+                    .unwrap_or(on_plane)
+            };
+            let distance = on_face.distance_squared(point);
+            if distance < closest.1 {
+                closest = (on_face, distance);
+            }
+        }
+        closest.0
+    }
+}
+
+// Whether two edges of solid polyhedra can touch, which is when the arcs their faces make on
+// the unit sphere cross, with b's faces turned inside out
+fn edges_can_touch(a: &Poly, ea: &HullEdge, b: &Poly, eb: &HullEdge) -> bool {
+    let (na, nb) = (a.faces[ea.faces[0]].normal, a.faces[ea.faces[1]].normal);
+    let (nc, nd) = (-b.faces[eb.faces[0]].normal, -b.faces[eb.faces[1]].normal);
+    let (bxa, dxc) = (nb.cross(na), nd.cross(nc));
+    let (cba, dba) = (nc.dot(bxa), nd.dot(bxa));
+    let (adc, bdc) = (na.dot(dxc), nb.dot(dxc));
+    cba * dba < 0.0 && adc * bdc < 0.0 && cba * bdc > 0.0
+}
+
+// Face of p the other is least deep past, None when one separates them
+fn shallowest_face(p: &Poly, other: &Poly) -> Option<(usize, f32)> {
+    let mut best = (0, f32::MIN);
+    for (i, face) in p.faces.iter().enumerate() {
+        let separation = other.project(face.normal).0 - face.offset;
+        if separation > 0.0 {
+            return None;
+        }
+        if separation > best.1 {
+            best = (i, separation);
+        }
+    }
+    Some(best)
+}
+
+// Separating axis test like cuboid_cuboid, for any pair of convex polyhedra
+fn poly_poly(a: &Poly, b: &Poly) -> Option<ContactManifold> {
+    let (face_a, separation_a) = shallowest_face(a, b)?;
+    let (face_b, separation_b) = shallowest_face(b, a)?;
+
+    let mut edge: Option<(&HullEdge, &HullEdge, f32, Vec3)> = None;
+    for ea in a.edges.iter() {
+        let (a0, a1) = a.edge(ea);
+        for eb in b.edges.iter() {
+            if a.solid && b.solid && !edges_can_touch(a, ea, b, eb) {
+                continue;
+            }
+            let (b0, b1) = b.edge(eb);
+            let axis = (a1 - a0).cross(b1 - b0);
+            // parallel edges are already covered by the face axes
+            let axis = match axis.try_normalize() {
+                Some(axis) if axis.length_squared() > 0.0 => axis,
+                _ => continue,
+            };
+            let ((min_a, max_a), (min_b, max_b)) = (a.project(axis), b.project(axis));
+            let (separation, normal) = if min_b - max_a >= min_a - max_b {
+                (min_b - max_a, axis)
+            } else {
+                (min_a - max_b, -axis)
+            };
+            if separation > 0.0 {
+                return None;
+            }
+            if edge.map_or(true, |(_, _, best, _)| separation > best) {
+                edge = Some((ea, eb, separation, normal));
+            }
+        }
+    }
+
+    // prefer faces, of a over b, edge contacts only win when clearly shallower
+    let separation = separation_a.max(separation_b);
+    match edge {
+        Some((ea, eb, edge_separation, normal))
+            if edge_separation > separation * 0.95 + 1e-3 =>
+        {
+            let (a0, a1) = a.edge(ea);
+            let (b0, b1) = b.edge(eb);
+            let (point_a, point_b) = closest_segment_points(a0, a1, b0, b1);
+            Some(ContactManifold {
+                normal,
+                points: vec![ContactPoint {
+                    point_a,
+                    point_b,
+                    depth: -edge_separation,
+                }],
+            })
+        }
+        _ if separation_b > separation_a * 0.98 + 1e-3 => {
+            Some(poly_face_contact(b, a, face_b).flipped())
+        }
+        _ => Some(poly_face_contact(a, b, face_a)),
+    }
+}
+
+// Clips the face of the incident poly facing most against the reference face, like face_contact
+fn poly_face_contact(reference: &Poly, incident: &Poly, face: usize) -> ContactManifold {
+    let face = &reference.faces[face];
+    let normal = face.normal;
+    let incident_face = incident.faces.iter().min_by(|a, b| {
+        let (da, db) = (a.normal.dot(normal), b.normal.dot(normal));
+        da.partial_cmp(&db).unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let mut polygon: Vec<Vec3> = incident_face
+        .map(|f| f.vertices.iter().map(|i| incident.vertices[*i]).collect())
+        .unwrap_or_default();
+
+    for (k, i) in face.vertices.iter().enumerate() {
+        let start = reference.vertices[*i];
+        let end = reference.vertices[face.vertices[(k + 1) % face.vertices.len()]];
+        let side = (end - start).cross(normal).normalize_or_zero();
+        polygon = clip_polygon(&polygon, side, side.dot(start));
+        if polygon.is_empty() {
+            break;
+        }
+    }
+
+    let points = polygon
+        .into_iter()
+        .filter_map(|p| {
+            let separation = normal.dot(p) - face.offset;
+            (separation <= 0.0).then(|| ContactPoint {
+                point_a: p - normal * separation,
+                point_b: p,
+                depth: -separation,
+            })
+        })
+        .collect();
+
+    ContactManifold { normal, points }
+}
+
+// Sphere or capsule against a polyhedron, normal points from the rounded shape. Separating
+// axes catch the core of the shape sinking in, otherwise the closest points between the
+// core and the surface decide the contact
+fn rounded_poly(r: &Rounded, p: &Poly) -> Option<ContactManifold> {
+    let dir = r.end - r.start;
+
+    // the axis out of p the core is least deep along
+    let mut best: Option<(Vec3, f32)> = None;
+    for face in p.faces.iter() {
+        let separation = face.normal.dot(r.start).min(face.normal.dot(r.end)) - face.offset;
+        if separation > r.radius {
+            return None;
+        }
+        if best.map_or(true, |(_, b)| separation > b) {
+            best = Some((face.normal, separation));
+        }
+    }
+    for edge in p.edges.iter() {
+        let (v0, v1) = p.edge(edge);
+        let axis = match dir.cross(v1 - v0).try_normalize() {
+            Some(axis) => axis,
+            None => continue,
+        };
+        let (min_p, max_p) = p.project(axis);
+        let (s0, s1) = (axis.dot(r.start), axis.dot(r.end));
+        let (separation, axis) = if s0.min(s1) - max_p >= min_p - s0.max(s1) {
+            (s0.min(s1) - max_p, axis)
+        } else {
+            (min_p - s0.max(s1), -axis)
+        };
+        if separation > r.radius {
+            return None;
+        }
+        if best.map_or(true, |(_, b)| separation > b) {
+            best = Some((axis, separation));
+        }
+    }
+    let (axis, separation) = best?;
+
+    let (axis, on_segment, on_poly, separation) = if separation < 0.0 {
+        // push the deepest end of the core back out
+        let deepest = if axis.dot(r.start) <= axis.dot(r.end) { r.start } else { r.end };
+        (axis, deepest, deepest - axis * separation, separation)
+    } else {
+        // the core is clear, find how close it comes
+        let ends = [r.start, r.end].map(|q| (q, p.closest_point(q)));
+        let edges = p.edges.iter().map(|edge| {
+            let (v0, v1) = p.edge(edge);
+            closest_segment_points(r.start, r.end, v0, v1)
+        });
+        let (on_segment, on_poly) = ends
+            .into_iter()
+            .chain(edges)
+            .min_by(|a, b| {
+                let (da, db) = (a.0.distance_squared(a.1), b.0.distance_squared(b.1));
+                da.partial_cmp(&db).unwrap_or(std::cmp::Ordering::Equal)
+            })?;
+        let distance = on_segment.distance(on_poly);
+        if distance > r.radius {
+            return None;
+        }
+        let axis = ((on_segment - on_poly) / distance).try_normalize().unwrap_or(axis);
+        (axis, on_segment, on_poly, distance)
+    };
+
+    capsule_face_contact(r, p, axis).or_else(|| {
+        Some(ContactManifold {
+            normal: -axis,
+            points: vec![ContactPoint {
+                point_a: on_segment - axis * r.radius,
+                point_b: on_poly,
+                depth: r.radius - separation,
+            }],
+        })
+    })
+}
+
+// A capsule lying along a face touches it along a line, the ends of the part over the face
+// are both kept so it rests flat instead of rocking on one point
+fn capsule_face_contact(r: &Rounded, p: &Poly, axis: Vec3) -> Option<ContactManifold> {
+    let dir = r.end - r.start;
+    let length = dir.length();
+    let face = p.faces.iter().max_by(|a, b| {
+        let (da, db) = (a.normal.dot(axis), b.normal.dot(axis));
+        da.partial_cmp(&db).unwrap_or(std::cmp::Ordering::Equal)
+    })?;
+    if length <= f32::EPSILON
+        || face.normal.dot(axis) < 0.98
+        || (dir / length).dot(face.normal).abs() > 0.2
+    {
+        return None;
+    }
+
+    // clip the core to the sides of the face
+    let (mut t0, mut t1) = (0.0_f32, 1.0_f32);
+    for (k, i) in face.vertices.iter().enumerate() {
+        let start = p.vertices[*i];
+        let end = p.vertices[face.vertices[(k + 1) % face.vertices.len()]];
+        let side = (end - start).cross(face.normal);
+        let (outside, along) = (side.dot(r.start - start), side.dot(dir));
+        if along.abs() <= f32::EPSILON {
+            if outside > 0.0 {
+                return None;
+            }
+            continue;
+        }
+        let t = -outside / along;
+        if along > 0.0 {
+            t1 = t1.min(t);
+        } else {
+            t0 = t0.max(t);
+        }
+    }
+    if t0 > t1 {
+        return None;
+    }
+
+    let points = [t0, t1]
+        .into_iter()
+        .filter_map(|t| {
+            let q = r.start + dir * t;
+            let height = face.normal.dot(q) - face.offset;
+            (height < r.radius).then(|| ContactPoint {
+                point_a: q - face.normal * r.radius,
+                point_b: q - face.normal * height,
+                depth: r.radius - height,
+            })
+        })
+        .collect::<Vec<_>>();
+    (!points.is_empty()).then(|| ContactManifold {
+        normal: -face.normal,
+        points,
+    })
+}
+
+// Closest points between segments p1-q1 and p2-q2, either may be a single point
+fn closest_segment_points(p1: Vec3, q1: Vec3, p2: Vec3, q2: Vec3) -> (Vec3, Vec3) {
+    let (d1, d2, r) = (q1 - p1, q2 - p2, p1 - p2);
+    let (a, e, f) = (d1.dot(d1), d2.dot(d2), d2.dot(r));
+    let (s, t) = if a <= f32::EPSILON && e <= f32::EPSILON {
+        (0.0, 0.0)
+    } else if a <= f32::EPSILON {
+        (0.0, (f / e).clamp(0.0, 1.0))
+    } else {
+        let c = d1.dot(r);
+        if e <= f32::EPSILON {
+            ((-c / a).clamp(0.0, 1.0), 0.0)
+        } else {
+            let b = d1.dot(d2);
+            let denom = a * e - b * b;
+            // parallel segments, any point will do for s
+            let s = if denom > f32::EPSILON * a * e {
+                ((b * f - c * e) / denom).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let t = (b * s + f) / e;
+            if t < 0.0 {
+                ((-c / a).clamp(0.0, 1.0), 0.0)
+            } else if t > 1.0 {
+                (((b - c) / a).clamp(0.0, 1.0), 1.0)
+            } else {
+                (s, t)
+            }
+        }
+    };
+    (p1 + d1 * s, p2 + d2 * t)
+}
+
+// Corners of the other shape below the plane, normal points out of the plane. Rounded
+// shapes count from the ends of their core
+fn plane_contact(
+    trans: &Transform,
+    normal: Vec3,
+    other: &Collider,
+    other_trans: &Transform,
+) -> Option<ContactManifold> {
+    let normal = trans.rotation * normal;
+    let offset = normal.dot(trans.translation);
+    let (corners, radius) = match Convex::new(other, other_trans)? {
+        Convex::Rounded(r) if r.start == r.end => (vec![r.start], r.radius),
+        Convex::Rounded(r) => (vec![r.start, r.end], r.radius),
+        Convex::Solid(p) => (p.vertices, 0.0),
+    };
+    let points = corners
+        .into_iter()
+        .filter_map(|c| {
+            let height = normal.dot(c) - offset;
+            (height < radius).then(|| ContactPoint {
+                point_a: c - normal * height,
+                point_b: c - normal * radius,
+                depth: radius - height,
+            })
+        })
+        .collect::<Vec<_>>();
+    (!points.is_empty()).then(|| ContactManifold { normal, points })
+}
+
+// Tests the triangles near the other shape one by one. Contacts facing about the same way
+// as the deepest are merged into its manifold, the rest wait for it to be resolved
+fn trimesh_contact(
+    mesh: &TriMesh,
+    trans: &Transform,
+    other: &Collider,
+    other_trans: &Transform,
+) -> Option<ContactManifold> {
+    let shape = Convex::new(other, other_trans)?;
+    let inv = trans.rotation.inverse();
+    let local = Transform {
+        translation: inv * (other_trans.translation - trans.translation),
+        rotation: inv * other_trans.rotation,
+        ..*other_trans
+    };
+
+    let manifolds = mesh
+        .query(&other.aabb(&local))
+        .filter_map(|i| {
+            let corners = mesh.triangle(i).map(|v| trans.translation + trans.rotation * v);
+            let triangle = Poly::triangle(corners)?;
+            match &shape {
+                Convex::Rounded(r) => rounded_poly(r, &triangle).map(ContactManifold::flipped),
+                Convex::Solid(p) => poly_poly(&triangle, p),
+            }
+        })
+        .collect::<Vec<_>>();
+    let deepest = manifolds.iter().max_by(|a, b| {
+        a.max_depth()
+            .partial_cmp(&b.max_depth())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    })?;
+    let normal = deepest.normal;
+    let points = manifolds
+        .iter()
+        .filter(|m| m.normal.dot(normal) > TRIMESH_MERGE_DOT)
+        .flat_map(|m| m.points.iter().copied())
+        .collect();
+    Some(ContactManifold { normal, points })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::{FRAC_PI_4, SQRT_2};
+
+    const EPS: f32 = 1e-4;
+
+    fn at(x: f32, y: f32, z: f32) -> Transform {
+        Transform::from_xyz(x, y, z)
+    }
+
+    fn assert_vec(actual: Vec3, expected: Vec3) {
+        assert!(actual.abs_diff_eq(expected, EPS), "{actual} != {expected}");
+    }
+
+    fn assert_depths(manifold: &ContactManifold, depth: f32) {
+        for p in manifold.points.iter() {
+            assert!((p.depth - depth).abs() < EPS, "depth {} != {depth}", p.depth);
+        }
+    }
+
+    #[test]
+    fn sphere_sphere() {
+        let sphere = Collider::Sphere { radius: 1.0 };
+        let m = sphere
+            .contact(&at(0.0, 0.0, 0.0), &sphere, &at(1.5, 0.0, 0.0))
+            .unwrap();
+        assert_vec(m.normal, Vec3::X);
+        assert_eq!(m.points.len(), 1);
+        assert_depths(&m, 0.5);
+        assert_vec(m.points[0].point_a, Vec3::new(1.0, 0.0, 0.0));
+        assert_vec(m.points[0].point_b, Vec3::new(0.5, 0.0, 0.0));
+
+        assert!(sphere
+            .contact(&at(0.0, 0.0, 0.0), &sphere, &at(2.5, 0.0, 0.0))
+            .is_none());
+    }
+
+    #[test]
+    fn sphere_cuboid() {
+        let sphere = Collider::Sphere { radius: 0.5 };
+        let cuboid = Collider::Cuboid { size: Vec3::ONE };
+
+        // sphere resting on top of the box, the normal points from a to b either way
+        let m = sphere
+            .contact(&at(0.0, 1.3, 0.0), &cuboid, &at(0.0, 0.0, 0.0))
+            .unwrap();
+        assert_vec(m.normal, -Vec3::Y);
+        assert_eq!(m.points.len(), 1);
+        assert_depths(&m, 0.2);
+        assert_vec(m.points[0].point_a, Vec3::new(0.0, 0.8, 0.0));
+        assert_vec(m.points[0].point_b, Vec3::new(0.0, 1.0, 0.0));
+
+        let m = cuboid
+            .contact(&at(0.0, 0.0, 0.0), &sphere, &at(0.0, 1.3, 0.0))
+            .unwrap();
+        assert_vec(m.normal, Vec3::Y);
+        assert_depths(&m, 0.2);
+        assert_vec(m.points[0].point_a, Vec3::new(0.0, 1.0, 0.0));
+
+        assert!(sphere
+            .contact(&at(0.0, 1.6, 0.0), &cuboid, &at(0.0, 0.0, 0.0))
+            .is_none());
+    }
+
+    #[test]
+    fn cuboid_cuboid_face() {
+        let cuboid = Collider::Cuboid {
+            size: Vec3::splat(0.5),
+        };
+        let m = cuboid
+            .contact(&at(0.0, 0.0, 0.0), &cuboid, &at(0.25, 0.9, 0.0))
+            .unwrap();
+        assert_vec(m.normal, Vec3::Y);
+        // the top face of a clipped by the bottom face of b
+        assert_eq!(m.points.len(), 4);
+        assert_depths(&m, 0.1);
+        for p in m.points.iter() {
+            assert!((p.point_a.y - 0.5).abs() < EPS);
+            assert!((p.point_b.y - 0.4).abs() < EPS);
+            assert!(p.point_b.x > -0.25 - EPS && p.point_b.x < 0.5 + EPS);
+        }
+
+        assert!(cuboid
+            .contact(&at(0.0, 0.0, 0.0), &cuboid, &at(0.25, 1.1, 0.0))
+            .is_none());
+    }
+
+    #[test]
+    fn cuboid_cuboid_edge() {
+        let cuboid = Collider::Cuboid {
+            size: Vec3::splat(0.5),
+        };
+        // a has an edge along z on top, b an edge along x underneath, crossing above the origin
+        let a = Transform::from_rotation(Quat::from_rotation_z(FRAC_PI_4));
+        let b = Transform::from_xyz(0.0, 1.4, 0.0)
+            .with_rotation(Quat::from_rotation_x(FRAC_PI_4));
+        let m = cuboid.contact(&a, &cuboid, &b).unwrap();
+        assert_vec(m.normal, Vec3::Y);
+        assert_eq!(m.points.len(), 1);
+        // each box reaches half its diagonal above or below its center
+        let reach = 0.5 * SQRT_2;
+        assert_depths(&m, 2.0 * reach - 1.4);
+        assert_vec(m.points[0].point_a, Vec3::new(0.0, reach, 0.0));
+        assert_vec(m.points[0].point_b, Vec3::new(0.0, 1.4 - reach, 0.0));
+    }
+
+    #[test]
+    fn shape_plane() {
+        let plane = Collider::Plane { normal: Vec3::Y };
+        let cuboid = Collider::Cuboid {
+            size: Vec3::splat(0.5),
+        };
+        let sphere = Collider::Sphere { radius: 0.5 };
+
+        // the four bottom corners of the box sink into the plane
+        let m = plane
+            .contact(&at(0.0, 0.0, 0.0), &cuboid, &at(0.0, 0.4, 0.0))
+            .unwrap();
+        assert_vec(m.normal, Vec3::Y);
+        assert_eq!(m.points.len(), 4);
+        assert_depths(&m, 0.1);
+        for p in m.points.iter() {
+            assert!(p.point_a.y.abs() < EPS);
+            assert!((p.point_b.y + 0.1).abs() < EPS);
+        }
+
+        let m = sphere
+            .contact(&at(1.0, 0.4, 2.0), &plane, &at(0.0, 0.0, 0.0))
+            .unwrap();
+        assert_vec(m.normal, -Vec3::Y);
+        assert_eq!(m.points.len(), 1);
+        assert_depths(&m, 0.1);
+        assert_vec(m.points[0].point_a, Vec3::new(1.0, -0.1, 2.0));
+        assert_vec(m.points[0].point_b, Vec3::new(1.0, 0.0, 2.0));
+
+        assert!(plane
+            .contact(&at(0.0, 0.0, 0.0), &sphere, &at(0.0, 0.6, 0.0))
+            .is_none());
+    }
+}
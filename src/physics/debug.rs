@@ -0,0 +1,362 @@
+use std::f32::consts::{PI, TAU};
+
+use bevy::{pbr::NotShadowCaster, prelude::*, render::render_resource::PrimitiveTopology};
+
+use super::{
+    owning_body, tangent_basis, Aabb, BodyHierarchy, BroadPhase, CenterOfMassWorld, Collider,
+    Contacts, Kinematic, LinearVelocity, Sensor, Sleeping, Static,
+};
+
+const CIRCLE_SEGMENTS: usize = 24;
+// Planes are drawn as a square this far out from their origin
+const PLANE_DRAW_EXTENT: f32 = 5.0;
+const NORMAL_LENGTH: f32 = 0.25;
+const POINT_SIZE: f32 = 0.05;
+
+/// Draws what the physics sees as lines over the scene
+#[derive(Default)]
+pub struct PhysicsDebugPlugin;
+
+impl Plugin for PhysicsDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PhysicsDebug>()
+            .add_system(toggle_physics_debug_system)
+            .add_system_to_stage(CoreStage::PostUpdate, physics_debug_system);
+    }
+}
+
+/// What the debug plugin draws, nothing until enabled
+pub struct PhysicsDebug {
+    pub enabled: bool,
+    /// Flips enabled when pressed
+    pub toggle_key: Option<KeyCode>,
+    /// Wireframes coloured by whether the body is dynamic, static, kinematic, asleep or a sensor
+    pub colliders: bool,
+    /// Both points of each contact and the normal
+    pub contacts: bool,
+    pub velocities: bool,
+    pub aabbs: bool,
+    /// Nodes of the broad phase tree, the sweep and prune broad phase has none
+    pub broad_phase: bool,
+    /// Seconds of movement a velocity line covers
+    pub velocity_scale: f32,
+}
+
+impl Default for PhysicsDebug {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            toggle_key: Some(KeyCode::F3),
+            colliders: true,
+            contacts: true,
+            velocities: true,
+            aabbs: false,
+            broad_phase: false,
+            velocity_scale: 0.25,
+        }
+    }
+}
+
+/// Each kind of line is one mesh with a flat colour
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugLineKind {
+    Dynamic,
+    Static,
+    Kinematic,
+    Sleeping,
+    Sensor,
+    Contact,
+    Velocity,
+    Aabb,
+    BroadPhase,
+}
+
+impl DebugLineKind {
+    pub const ALL: [DebugLineKind; 9] = [
+        DebugLineKind::Dynamic,
+        DebugLineKind::Static,
+        DebugLineKind::Kinematic,
+        DebugLineKind::Sleeping,
+        DebugLineKind::Sensor,
+        DebugLineKind::Contact,
+        DebugLineKind::Velocity,
+        DebugLineKind::Aabb,
+        DebugLineKind::BroadPhase,
+    ];
+
+    pub fn color(&self) -> Color {
+        match self {
+            DebugLineKind::Dynamic => Color::rgb(0.2, 0.6, 1.0),
+            DebugLineKind::Static => Color::rgb(0.6, 0.6, 0.6),
+            DebugLineKind::Kinematic => Color::rgb(1.0, 0.8, 0.2),
+            DebugLineKind::Sleeping => Color::rgb(0.5, 0.3, 0.7),
+            DebugLineKind::Sensor => Color::rgb(0.3, 1.0, 0.3),
+            DebugLineKind::Contact => Color::rgb(1.0, 0.2, 0.2),
+            DebugLineKind::Velocity => Color::rgb(0.2, 1.0, 1.0),
+            DebugLineKind::Aabb => Color::rgb(1.0, 0.5, 0.1),
+            DebugLineKind::BroadPhase => Color::rgba(1.0, 1.0, 1.0, 0.3),
+        }
+    }
+}
+
+/// Line segments as pairs of world space points
+#[derive(Debug, Default, Clone)]
+pub struct DebugLines(pub Vec<Vec3>);
+
+impl DebugLines {
+    pub fn line(&mut self, a: Vec3, b: Vec3) {
+        self.0.extend([a, b]);
+    }
+
+    /// Part of a circle from angle from to angle to, measured from u towards v
+    pub fn arc(&mut self, center: Vec3, u: Vec3, v: Vec3, radius: f32, from: f32, to: f32) {
+        let point = |i: usize| {
+            let angle = from + (to - from) * i as f32 / CIRCLE_SEGMENTS as f32;
+            center + (u * angle.cos() + v * angle.sin()) * radius
+        };
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1));
+        }
+    }
+
+    pub fn circle(&mut self, center: Vec3, normal: Vec3, radius: f32) {
+        let [u, v] = tangent_basis(normal);
+        self.arc(center, u, v, radius, 0.0, TAU);
+    }
+
+    pub fn aabb(&mut self, aabb: &Aabb) {
+        let corner = |i: usize| {
+            let pick = |bit: usize, min: f32, max: f32| if i & bit != 0 { max } else { min };
+            Vec3::new(
+                pick(1, aabb.min.x, aabb.max.x),
+                pick(2, aabb.min.y, aabb.max.y),
+                pick(4, aabb.min.z, aabb.max.z),
+            )
+        };
+        self.box_edges(corner);
+    }
+
+    /// Small cross marking a point
+    pub fn point(&mut self, p: Vec3) {
+        for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+            self.line(p - axis * POINT_SIZE, p + axis * POINT_SIZE);
+        }
+    }
+
+    pub fn collider(&mut self, collider: &Collider, trans: &Transform) {
+        let to_world = |p: Vec3| trans.translation + trans.rotation * p;
+        match collider {
+            Collider::Sphere { radius } => {
+                for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+                    self.circle(trans.translation, trans.rotation * axis, *radius);
+                }
+            }
+            Collider::Cuboid { size } => {
+                let corner = |i: usize| {
+                    let sign = |bit: usize| if i & bit != 0 { 1.0 } else { -1.0 };
+                    to_world(*size * Vec3::new(sign(1), sign(2), sign(4)))
+                };
+                self.box_edges(corner);
+            }
+            Collider::Capsule {
+                half_height,
+                radius,
+            } => {
+                let [x, y, z] = [Vec3::X, Vec3::Y, Vec3::Z].map(|axis| trans.rotation * axis);
+                let top = to_world(Vec3::Y * *half_height);
+                let bottom = to_world(-Vec3::Y * *half_height);
+                for (end, up) in [(top, y), (bottom, -y)] {
+                    self.circle(end, y, *radius);
+                    self.arc(end, x, up, *radius, 0.0, PI);
+                    self.arc(end, z, up, *radius, 0.0, PI);
+                }
+                for side in [x, -x, z, -z] {
+                    self.line(bottom + side * *radius, top + side * *radius);
+                }
+            }
+            Collider::Plane { normal } => {
+                let normal = trans.rotation * *normal;
+                let [u, v] = tangent_basis(normal).map(|t| t * PLANE_DRAW_EXTENT);
+                let c = trans.translation;
+                let corners = [c + u + v, c - u + v, c - u - v, c + u - v];
+                for i in 0..4 {
+                    self.line(corners[i], corners[(i + 1) % 4]);
+                }
+                self.line(c - u, c + u);
+                self.line(c - v, c + v);
+                self.line(c, c + normal);
+            }
+            Collider::ConvexHull(hull) => {
+                let points = hull.points();
+                for edge in hull.edges() {
+                    self.line(to_world(points[edge.a]), to_world(points[edge.b]));
+                }
+            }
+            Collider::TriMesh(mesh) => {
+                for i in 0..mesh.triangles().len() {
+                    let [a, b, c] = mesh.triangle(i).map(to_world);
+                    self.line(a, b);
+                    self.line(b, c);
+                    self.line(c, a);
+                }
+            }
+            Collider::Compound(_) => {
+                for (_, part, part_trans) in collider.parts(trans) {
+                    self.collider(part, &part_trans);
+                }
+            }
+        }
+    }
+
+    // Corners numbered by bits, x in the lowest, joined along each axis
+    fn box_edges(&mut self, corner: impl Fn(usize) -> Vec3) {
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corner(i), corner(i | bit));
+                }
+            }
+        }
+    }
+}
+
+// Flips the debug drawing on and off with the toggle key
+pub fn toggle_physics_debug_system(keys: Res<Input<KeyCode>>, mut debug: ResMut<PhysicsDebug>) {
+    if let Some(key) = debug.toggle_key {
+        if keys.just_pressed(key) {
+            debug.enabled = !debug.enabled;
+        }
+    }
+}
+
+// Rebuilds the line meshes from this frame's colliders, contacts and broad phase. The
+// meshes are made the first time anything is drawn and hidden while drawing is off
+#[allow(clippy::too_many_arguments)]
+pub fn physics_debug_system(
+    mut commands: Commands,
+    debug: Res<PhysicsDebug>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut line_meshes: Query<(&DebugLineKind, &Handle<Mesh>, &mut Visibility)>,
+    colliders: Query<(
+        Entity,
+        &Collider,
+        &Transform,
+        (
+            Option<&Static>,
+            Option<&Kinematic>,
+            Option<&Sleeping>,
+            Option<&Sensor>,
+        ),
+    )>,
+    velocities: Query<(&CenterOfMassWorld, &LinearVelocity), Without<Sleeping>>,
+    hierarchy: BodyHierarchy,
+    contacts: Res<Contacts>,
+    broad_phase: Res<BroadPhase>,
+) {
+    if !debug.enabled {
+        for (_, _, mut visibility) in line_meshes.iter_mut() {
+            visibility.is_visible = false;
+        }
+        return;
+    }
+    if line_meshes.is_empty() {
+        spawn_line_meshes(&mut commands, &mut meshes, &mut materials);
+        return;
+    }
+
+    let mut lines = DebugLineKind::ALL.map(|_| DebugLines::default());
+
+    for (e, collider, trans, (fixed, kinematic, sleeping, sensor)) in colliders.iter() {
+        // parts are drawn with their compound body
+        if owning_body(e, &hierarchy).is_some() {
+            continue;
+        }
+        if debug.colliders {
+            let kind = match (fixed, kinematic, sleeping, sensor) {
+                (_, _, _, Some(_)) => DebugLineKind::Sensor,
+                (_, _, Some(_), _) => DebugLineKind::Sleeping,
+                (Some(_), ..) => DebugLineKind::Static,
+                (_, Some(_), ..) => DebugLineKind::Kinematic,
+                _ => DebugLineKind::Dynamic,
+            };
+            lines[kind as usize].collider(collider, trans);
+        }
+        if debug.aabbs {
+            lines[DebugLineKind::Aabb as usize].aabb(&collider.aabb(trans));
+        }
+    }
+
+    if debug.contacts {
+        let contact_lines = &mut lines[DebugLineKind::Contact as usize];
+        for contact in contacts.0.iter() {
+            for p in contact.manifold.points.iter() {
+                contact_lines.point(p.point_a);
+                contact_lines.point(p.point_b);
+                contact_lines.line(p.point_a, p.point_b);
+                let mid = (p.point_a + p.point_b) * 0.5;
+                contact_lines.line(mid, mid + contact.manifold.normal * NORMAL_LENGTH);
+            }
+        }
+    }
+
+    if debug.velocities {
+        for (com, velocity) in velocities.iter() {
+            let end = com.0 + velocity.0 * debug.velocity_scale;
+            lines[DebugLineKind::Velocity as usize].line(com.0, end);
+        }
+    }
+
+    if debug.broad_phase {
+        for aabb in broad_phase.strategy.node_bounds() {
+            lines[DebugLineKind::BroadPhase as usize].aabb(&aabb);
+        }
+    }
+
+    for (kind, handle, mut visibility) in line_meshes.iter_mut() {
+        let points = &lines[*kind as usize].0;
+        visibility.is_visible = !points.is_empty();
+        if let Some(mesh) = meshes.get_mut(handle) {
+            set_line_mesh(mesh, points);
+        }
+    }
+}
+
+fn spawn_line_meshes(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+) {
+    for kind in DebugLineKind::ALL {
+        let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+        set_line_mesh(&mut mesh, &[]);
+        let color = kind.color();
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(mesh),
+                material: materials.add(StandardMaterial {
+                    base_color: color,
+                    unlit: true,
+                    alpha_mode: if color.a() < 1.0 {
+                        AlphaMode::Blend
+                    } else {
+                        AlphaMode::Opaque
+                    },
+                    ..default()
+                }),
+                visibility: Visibility { is_visible: false },
+                ..default()
+            })
+            .insert(kind)
+            .insert(NotShadowCaster)
+            .insert(Name::new(format!("Physics Debug {:?}", kind)));
+    }
+}
+
+// The standard material wants normals and uvs on every vertex, lines ignore them
+fn set_line_mesh(mesh: &mut Mesh, points: &[Vec3]) {
+    let positions = points.iter().map(|p| p.to_array()).collect::<Vec<_>>();
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 1.0, 0.0]; positions.len()]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; positions.len()]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+}
@@ -0,0 +1,103 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{ContactManifold, Contacts, Sensor, Sleeping, Static};
+
+/// Opts an entity in to CollisionStarted and CollisionEnded events for its contacts,
+/// sensors always send them
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct CollisionEvents;
+
+/// Summary of a contact, taken at its deepest point
+#[derive(Debug, Clone, Copy)]
+pub struct ContactInfo {
+    /// World space, pointing from the first entity to the second
+    pub normal: Vec3,
+    /// World space point between the two surfaces
+    pub point: Vec3,
+    pub depth: f32,
+}
+
+impl From<&ContactManifold> for ContactInfo {
+    fn from(manifold: &ContactManifold) -> Self {
+        let deepest = manifold
+            .points
+            .iter()
+            .max_by(|a, b| a.depth.partial_cmp(&b.depth).unwrap_or(std::cmp::Ordering::Equal));
+        Self {
+            normal: manifold.normal,
+            point: deepest.map_or(Vec3::ZERO, |p| (p.point_a + p.point_b) * 0.5),
+            depth: deepest.map_or(0.0, |p| p.depth),
+        }
+    }
+}
+
+/// Two entities started touching, lowest entity first
+#[derive(Debug, Clone)]
+pub struct CollisionStarted(pub Entity, pub Entity, pub ContactInfo);
+
+/// Two entities stopped touching, or one of them went away
+#[derive(Debug, Clone)]
+pub struct CollisionEnded(pub Entity, pub Entity);
+
+/// Pairs touching as of the last step, and whether either side was listening for events
+/// when they started touching
+#[derive(Debug, Default, Clone)]
+pub struct CollidingPairs(pub HashMap<(Entity, Entity), bool>);
+
+impl CollidingPairs {
+    pub fn contains(&self, a: Entity, b: Entity) -> bool {
+        let pair = if a < b { (a, b) } else { (b, a) };
+        self.0.contains_key(&pair)
+    }
+}
+
+// Diffs this step's contacts against the last to find pairs that started or stopped touching
+pub fn collision_events_system(
+    contacts: Res<Contacts>,
+    mut colliding: ResMut<CollidingPairs>,
+    listeners: Query<(), Or<(With<CollisionEvents>, With<Sensor>)>>,
+    resting: Query<(), Or<(With<Sleeping>, With<Static>)>>,
+    mut started_events: EventWriter<CollisionStarted>,
+    mut ended_events: EventWriter<CollisionEnded>,
+) {
+    let wants_events = |a: Entity, b: Entity| listeners.get(a).is_ok() || listeners.get(b).is_ok();
+
+    let mut touching = HashMap::default();
+    for contact in contacts.0.iter() {
+        let pair = (contact.entity_a, contact.entity_b);
+        // compound bodies can touch with several parts at once
+        if touching.contains_key(&pair) {
+            continue;
+        }
+        let listening = match colliding.0.get(&pair) {
+            Some(listening) => *listening,
+            None => {
+                let listening = wants_events(pair.0, pair.1);
+                if listening {
+                    started_events.send(CollisionStarted(
+                        pair.0,
+                        pair.1,
+                        ContactInfo::from(&contact.manifold),
+                    ));
+                }
+                listening
+            }
+        };
+        touching.insert(pair, listening);
+    }
+
+    // the narrow phase skips pairs that are both at rest, they are still touching
+    for (&(a, b), &listening) in colliding.0.iter() {
+        if resting.get(a).is_ok() && resting.get(b).is_ok() {
+            touching.insert((a, b), listening);
+        }
+    }
+
+    // decided by who listened at the start, the listener may be despawned by now
+    for (&(a, b), &listening) in colliding.0.iter() {
+        if listening && !touching.contains_key(&(a, b)) {
+            ended_events.send(CollisionEnded(a, b));
+        }
+    }
+    colliding.0 = touching;
+}
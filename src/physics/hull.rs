@@ -0,0 +1,329 @@
+use std::cmp::Ordering;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{mesh_positions, tangent_basis};
+
+// Triangles this close to parallel and in the same plane are merged into one face
+const COPLANAR_DOT: f32 = 1.0 - 1e-4;
+
+/// Convex polyhedron around a set of points, in the local space of the collider
+#[derive(Debug, Clone)]
+pub struct ConvexHull {
+    points: Vec<Vec3>,
+    faces: Vec<HullFace>,
+    edges: Vec<HullEdge>,
+    volume: f32,
+    center_of_mass: Vec3,
+    // about the center of mass, for a mass of 1
+    inertia: Mat3,
+    min_width: f32,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct HullFace {
+    /// Outward facing
+    pub normal: Vec3,
+    /// normal . p for any point p on the face
+    pub offset: f32,
+    /// Corners, counter clockwise seen from outside
+    pub vertices: Vec<usize>,
+}
+
+/// Edge between two corners and the two faces either side of it
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct HullEdge {
+    pub a: usize,
+    pub b: usize,
+    pub faces: [usize; 2],
+}
+
+impl ConvexHull {
+    /// Smallest convex shape holding all the points, None when they are all on one plane
+    pub fn new(points: &[Vec3]) -> Option<Self> {
+        let (min, max) = points.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), p| (min.min(*p), max.max(*p)),
+        );
+        let eps = 1e-5 * (max - min).max_element().max(f32::EPSILON);
+        let triangles = hull_triangles(points, eps)?;
+
+        // only keep points on the hull, merging triangles sharing a plane into faces
+        let mut remap = HashMap::<usize, usize>::default();
+        let mut hull_points = Vec::new();
+        let mut faces = Vec::<HullFace>::new();
+        for (tri, normal) in triangles {
+            let tri = tri.map(|i| {
+                *remap.entry(i).or_insert_with(|| {
+                    hull_points.push(points[i]);
+                    hull_points.len() - 1
+                })
+            });
+            let offset = normal.dot(hull_points[tri[0]]);
+            match faces
+                .iter_mut()
+                .find(|f| f.normal.dot(normal) > COPLANAR_DOT && (f.offset - offset).abs() < eps)
+            {
+                Some(face) => face.vertices.extend(tri),
+                None => faces.push(HullFace {
+                    normal,
+                    offset,
+                    vertices: tri.to_vec(),
+                }),
+            }
+        }
+        for face in faces.iter_mut() {
+            wind_face(&hull_points, face);
+        }
+
+        let mut sides = HashMap::<(usize, usize), Vec<usize>>::default();
+        for (f, face) in faces.iter().enumerate() {
+            for (i, a) in face.vertices.iter().enumerate() {
+                let b = face.vertices[(i + 1) % face.vertices.len()];
+                sides.entry(((*a).min(b), (*a).max(b))).or_default().push(f);
+            }
+        }
+        let edges = sides
+            .into_iter()
+            .map(|((a, b), f)| HullEdge {
+                a,
+                b,
+                faces: [f[0], *f.get(1).unwrap_or(&f[0])],
+            })
+            .collect();
+
+        let (volume, center_of_mass, inertia) = mass_properties(&hull_points, &faces);
+        let min_width = faces
+            .iter()
+            .map(|f| {
+                let deepest = hull_points
+                    .iter()
+                    .map(|p| f.normal.dot(*p))
+                    .fold(f32::MAX, f32::min);
+                f.offset - deepest
+            })
+            .fold(f32::MAX, f32::min);
+
+        Some(Self {
+            points: hull_points,
+            faces,
+            edges,
+            volume,
+            center_of_mass,
+            inertia,
+            min_width,
+        })
+    }
+
+    /// Hull around every vertex of a mesh
+    pub fn from_mesh(mesh: &Mesh) -> Option<Self> {
+        Self::new(&mesh_positions(mesh)?)
+    }
+
+    /// Corners of the hull
+    pub fn points(&self) -> &[Vec3] {
+        &self.points
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.volume
+    }
+
+    pub fn center_of_mass(&self) -> Vec3 {
+        self.center_of_mass
+    }
+
+    /// Inertia tensor about the center of mass for a mass of 1
+    pub fn inertia(&self) -> Mat3 {
+        self.inertia
+    }
+
+    pub(crate) fn faces(&self) -> &[HullFace] {
+        &self.faces
+    }
+
+    pub(crate) fn edges(&self) -> &[HullEdge] {
+        &self.edges
+    }
+
+    // Thinnest the hull gets across any of its faces
+    pub(crate) fn min_width(&self) -> f32 {
+        self.min_width
+    }
+}
+
+// Incremental hull, every triangle wound counter clockwise seen from outside
+fn hull_triangles(points: &[Vec3], eps: f32) -> Option<Vec<([usize; 3], Vec3)>> {
+    let farthest = |score: &dyn Fn(Vec3) -> f32| {
+        (0..points.len())
+            .map(|i| (i, score(points[i])))
+            .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal))
+    };
+
+    // starting tetrahedron from points spread as far apart as possible
+    let (i0, _) = farthest(&|p| -p.x)?;
+    let p0 = points[i0];
+    let (i1, d1) = farthest(&|p| p.distance(p0))?;
+    let p1 = points[i1];
+    let (i2, d2) = farthest(&|p| (p1 - p0).normalize().cross(p - p0).length())?;
+    let p2 = points[i2];
+    let n = (p1 - p0).cross(p2 - p0).normalize();
+    let (i3, d3) = farthest(&|p| n.dot(p - p0).abs())?;
+    if d1 < eps || d2 < eps || d3 < eps {
+        return None;
+    }
+    let inside = (p0 + p1 + p2 + points[i3]) * 0.25;
+
+    let triangle = |tri: [usize; 3]| {
+        let [a, b, c] = tri.map(|i| points[i]);
+        let normal = (b - a).cross(c - a).normalize();
+        // facing away from a point inside the hull
+        if normal.dot(a - inside) < 0.0 {
+            ([tri[0], tri[2], tri[1]], -normal)
+        } else {
+            (tri, normal)
+        }
+    };
+    let mut triangles = vec![
+        triangle([i0, i1, i2]),
+        triangle([i0, i1, i3]),
+        triangle([i0, i2, i3]),
+        triangle([i1, i2, i3]),
+    ];
+
+    for (i, p) in points.iter().enumerate() {
+        let visible = |(tri, normal): &([usize; 3], Vec3)| normal.dot(*p - points[tri[0]]) > eps;
+        if !triangles.iter().any(visible) {
+            continue;
+        }
+        // the edges around the patch of triangles the point can see
+        let mut patch = Vec::new();
+        triangles.retain(|t| {
+            if visible(t) {
+                let [a, b, c] = t.0;
+                patch.extend([(a, b), (b, c), (c, a)]);
+                false
+            } else {
+                true
+            }
+        });
+        for (a, b) in patch.iter() {
+            if !patch.contains(&(*b, *a)) {
+                let [pa, pb] = [points[*a], points[*b]];
+                let normal = (pb - pa).cross(*p - pa).normalize_or_zero();
+                triangles.push(([*a, *b, i], normal));
+            }
+        }
+    }
+    Some(triangles)
+}
+
+// Sorts the corners of a merged face counter clockwise around its normal
+fn wind_face(points: &[Vec3], face: &mut HullFace) {
+    face.vertices.sort_unstable();
+    face.vertices.dedup();
+    let center = face
+        .vertices
+        .iter()
+        .fold(Vec3::ZERO, |sum, i| sum + points[*i])
+        / face.vertices.len() as f32;
+    let u = tangent_basis(face.normal)[0];
+    let v = face.normal.cross(u);
+    let angle = |i: &usize| {
+        let d = points[*i] - center;
+        d.dot(v).atan2(d.dot(u))
+    };
+    face.vertices
+        .sort_by(|a, b| angle(a).partial_cmp(&angle(b)).unwrap_or(Ordering::Equal));
+}
+
+// Volume, center of mass and unit mass inertia, summed over tetrahedra from an inside point
+fn mass_properties(points: &[Vec3], faces: &[HullFace]) -> (f32, Vec3, Mat3) {
+    let origin = points.iter().fold(Vec3::ZERO, |sum, p| sum + *p) / points.len() as f32;
+    let outer = |a: Vec3, b: Vec3| Mat3::from_cols(a * b.x, a * b.y, a * b.z);
+
+    let mut volume = 0.0;
+    let mut moment = Vec3::ZERO;
+    let mut covariance = Mat3::ZERO;
+    for face in faces.iter() {
+        let a = points[face.vertices[0]] - origin;
+        for pair in face.vertices[1..].windows(2) {
+            let (b, c) = (points[pair[0]] - origin, points[pair[1]] - origin);
+            let det = a.dot(b.cross(c));
+            let sum = a + b + c;
+            volume += det / 6.0;
+            moment += sum * det / 24.0;
+            covariance +=
+                (outer(sum, sum) + outer(a, a) + outer(b, b) + outer(c, c)) * (det / 120.0);
+        }
+    }
+    if volume <= f32::EPSILON {
+        return (0.0, origin, Mat3::ZERO);
+    }
+
+    // move the covariance to the center of mass, then turn it into an inertia tensor
+    let offset = moment / volume;
+    let covariance = covariance - outer(offset, offset) * volume;
+    let trace = covariance.x_axis.x + covariance.y_axis.y + covariance.z_axis.z;
+    let inertia = Mat3::from_diagonal(Vec3::splat(trace)) - covariance;
+    (volume, origin + offset, inertia * (1.0 / volume))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::Collider;
+
+    const EPS: f32 = 1e-4;
+
+    fn assert_mat(actual: Mat3, expected: Mat3) {
+        assert!(actual.abs_diff_eq(expected, EPS), "{actual} != {expected}");
+    }
+
+    #[test]
+    fn box_hull_matches_cuboid() {
+        let half = Vec3::new(1.0, 0.5, 0.25);
+        let center = Vec3::new(1.0, 2.0, 3.0);
+        // the corners, plus points inside and on a face that shouldn't change the hull
+        let mut points = (0..8)
+            .map(|i| {
+                let sign = Vec3::new(
+                    if i & 1 == 0 { -1.0 } else { 1.0 },
+                    if i & 2 == 0 { -1.0 } else { 1.0 },
+                    if i & 4 == 0 { -1.0 } else { 1.0 },
+                );
+                center + half * sign
+            })
+            .collect::<Vec<_>>();
+        points.push(center);
+        points.push(center + half * 0.5);
+        points.push(center + Vec3::new(half.x, 0.1, -0.1));
+        let hull = ConvexHull::new(&points).unwrap();
+
+        let cuboid = Collider::cuboid(half.x, half.y, half.z);
+        assert_eq!(hull.points().len(), 8);
+        assert_eq!(hull.faces().len(), 6);
+        assert_eq!(hull.edges().len(), 12);
+        assert!((hull.volume() - cuboid.volume()).abs() < EPS);
+        assert!(hull.center_of_mass().abs_diff_eq(center, EPS));
+        assert_mat(hull.inertia(), cuboid.get_inertia_tensor());
+        assert!((hull.min_width() - 2.0 * half.z).abs() < EPS);
+    }
+
+    #[test]
+    fn hull_from_mesh() {
+        let mesh = Mesh::from(shape::Box::new(2.0, 1.0, 0.5));
+        let hull = ConvexHull::from_mesh(&mesh).unwrap();
+        let cuboid = Collider::cuboid(1.0, 0.5, 0.25);
+        assert_eq!(hull.points().len(), 8);
+        assert!((hull.volume() - cuboid.volume()).abs() < EPS);
+        assert!(hull.center_of_mass().abs_diff_eq(Vec3::ZERO, EPS));
+        assert_mat(hull.inertia(), cuboid.get_inertia_tensor());
+    }
+
+    #[test]
+    fn flat_points_have_no_hull() {
+        let points = [Vec3::ZERO, Vec3::X, Vec3::Z, Vec3::new(1.0, 0.0, 1.0)];
+        assert!(ConvexHull::new(&points).is_none());
+    }
+}
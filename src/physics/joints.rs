@@ -0,0 +1,421 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+
+use super::tangent_basis;
+
+/// Drives a hinge or prismatic joint at a speed, with limited strength
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JointMotor {
+    /// Radians per second for hinges, units per second for prismatic joints
+    pub target_velocity: f32,
+    /// Largest torque or force the motor can apply
+    pub max_force: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JointKind {
+    /// Holds the bodies in the pose they were joined in
+    Fixed,
+    /// Keeps the anchors between min and max apart, a rope has a min of 0
+    Distance { min: f32, max: f32 },
+    /// Anchors stay together, free to turn any way
+    Ball,
+    /// Anchors stay together, turning only around the axis given in a's space.
+    /// Limits are angles from the joined pose
+    Hinge {
+        axis: Vec3,
+        limits: Option<(f32, f32)>,
+        motor: Option<JointMotor>,
+    },
+    /// Slides along the axis given in a's space without turning.
+    /// Limits are distances from the anchor of a
+    Prismatic {
+        axis: Vec3,
+        limits: Option<(f32, f32)>,
+        motor: Option<JointMotor>,
+    },
+}
+
+/// Constraint between two bodies, best kept on its own entity. Despawn it to let go
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct Joint {
+    pub body_a: Entity,
+    pub body_b: Entity,
+    pub kind: JointKind,
+    /// Attachment point in the local space of a
+    pub anchor_a: Vec3,
+    /// Attachment point in the local space of b
+    pub anchor_b: Vec3,
+    /// Force the joint breaks at, it is removed and a JointBroken sent
+    pub break_force: Option<f32>,
+    /// Let the two bodies collide with each other
+    pub collide_connected: bool,
+    // rotation of b relative to a when the joint was first solved
+    rest: Option<Quat>,
+}
+
+impl Joint {
+    pub fn new(body_a: Entity, body_b: Entity, kind: JointKind) -> Self {
+        Self {
+            body_a,
+            body_b,
+            kind,
+            anchor_a: Vec3::ZERO,
+            anchor_b: Vec3::ZERO,
+            break_force: None,
+            collide_connected: false,
+            rest: None,
+        }
+    }
+
+    pub fn fixed(body_a: Entity, body_b: Entity) -> Self {
+        Self::new(body_a, body_b, JointKind::Fixed)
+    }
+
+    pub fn distance(body_a: Entity, body_b: Entity, length: f32) -> Self {
+        Self::new(body_a, body_b, JointKind::Distance { min: length, max: length })
+    }
+
+    pub fn rope(body_a: Entity, body_b: Entity, length: f32) -> Self {
+        Self::new(body_a, body_b, JointKind::Distance { min: 0.0, max: length })
+    }
+
+    pub fn ball(body_a: Entity, body_b: Entity) -> Self {
+        Self::new(body_a, body_b, JointKind::Ball)
+    }
+
+    pub fn hinge(body_a: Entity, body_b: Entity, axis: Vec3) -> Self {
+        let kind = JointKind::Hinge {
+            axis: axis.normalize(),
+            limits: None,
+            motor: None,
+        };
+        Self::new(body_a, body_b, kind)
+    }
+
+    pub fn prismatic(body_a: Entity, body_b: Entity, axis: Vec3) -> Self {
+        let kind = JointKind::Prismatic {
+            axis: axis.normalize(),
+            limits: None,
+            motor: None,
+        };
+        Self::new(body_a, body_b, kind)
+    }
+
+    pub fn with_anchors(mut self, anchor_a: Vec3, anchor_b: Vec3) -> Self {
+        self.anchor_a = anchor_a;
+        self.anchor_b = anchor_b;
+        self
+    }
+
+    /// Only for hinge and prismatic joints
+    pub fn with_limits(mut self, min: f32, max: f32) -> Self {
+        if let JointKind::Hinge { limits, .. } | JointKind::Prismatic { limits, .. } =
+            &mut self.kind
+        {
+            *limits = Some((min, max));
+        }
+        self
+    }
+
+    /// Only for hinge and prismatic joints
+    pub fn with_motor(mut self, target_velocity: f32, max_force: f32) -> Self {
+        if let JointKind::Hinge { motor, .. } | JointKind::Prismatic { motor, .. } = &mut self.kind
+        {
+            *motor = Some(JointMotor {
+                target_velocity,
+                max_force,
+            });
+        }
+        self
+    }
+
+    pub fn with_break_force(mut self, break_force: f32) -> Self {
+        self.break_force = Some(break_force);
+        self
+    }
+
+    pub(crate) fn has_rest(&self) -> bool {
+        self.rest.is_some()
+    }
+
+    /// Remembers the pose the bodies are in as the one the joint holds them in
+    pub(crate) fn capture_rest(&mut self, trans_a: &Transform, trans_b: &Transform) {
+        self.rest = Some(trans_a.rotation.inverse() * trans_b.rotation);
+    }
+
+    /// Velocity constraints holding the joint together this step, errors are fed back
+    /// by bias_factor, the Baumgarte factor over the step time
+    pub(crate) fn constraints(
+        &self,
+        trans_a: &Transform,
+        com_a: Vec3,
+        trans_b: &Transform,
+        com_b: Vec3,
+        bias_factor: f32,
+        dt: f32,
+    ) -> JointConstraints {
+        let rest = self
+            .rest
+            .unwrap_or_else(|| trans_a.rotation.inverse() * trans_b.rotation);
+
+        let point_a = trans_a.translation + trans_a.rotation * self.anchor_a;
+        let point_b = trans_b.translation + trans_b.rotation * self.anchor_b;
+        let (ra, rb) = (point_a - com_a, point_b - com_b);
+        let d = point_b - point_a;
+
+        // moves the anchors along dir
+        let linear = |dir: Vec3, error: f32, min: f32, max: f32| JointRow {
+            lin_a: -dir,
+            ang_a: -ra.cross(dir),
+            lin_b: dir,
+            ang_b: rb.cross(dir),
+            bias: -bias_factor * error,
+            min_impulse: min,
+            max_impulse: max,
+        };
+        // turns b relative to a around axis
+        let angular = |axis: Vec3, error: f32, min: f32, max: f32| JointRow {
+            lin_a: Vec3::ZERO,
+            ang_a: -axis,
+            lin_b: Vec3::ZERO,
+            ang_b: axis,
+            bias: -bias_factor * error,
+            min_impulse: min,
+            max_impulse: max,
+        };
+        let motor = |row: JointRow, motor: JointMotor| JointRow {
+            bias: motor.target_velocity,
+            min_impulse: -motor.max_force * dt,
+            max_impulse: motor.max_force * dt,
+            ..row
+        };
+
+        let mut rows = Vec::new();
+        let mut pin = None;
+        let pinned = JointPin {
+            ra,
+            rb,
+            bias: -bias_factor * d,
+        };
+        let lock_rotation = |rows: &mut Vec<JointRow>| {
+            // b's turn away from its rest pose, as a small rotation vector
+            let error = trans_b.rotation * (trans_a.rotation * rest).inverse();
+            let error = if error.w < 0.0 { -error } else { error };
+            let error = 2.0 * Vec3::new(error.x, error.y, error.z);
+            for axis in [Vec3::X, Vec3::Y, Vec3::Z] {
+                rows.push(angular(axis, error.dot(axis), f32::NEG_INFINITY, f32::INFINITY));
+            }
+        };
+
+        match self.kind {
+            JointKind::Fixed => {
+                pin = Some(pinned);
+                lock_rotation(&mut rows);
+            }
+            JointKind::Ball => pin = Some(pinned),
+            JointKind::Distance { min, max } => {
+                let length = d.length();
+                let dir = if length > f32::EPSILON { d / length } else { Vec3::Y };
+                rows.extend(limit(length, (min, max), |error, lo, hi| linear(dir, error, lo, hi)));
+            }
+            JointKind::Hinge {
+                axis,
+                limits,
+                motor: hinge_motor,
+            } => {
+                pin = Some(pinned);
+
+                // keep b's copy of the axis lined up with a's
+                let axis_b = rest.inverse() * axis;
+                let world_a = trans_a.rotation * axis;
+                let world_b = trans_b.rotation * axis_b;
+                let misalignment = world_a.cross(world_b);
+                for t in tangent_basis(world_a) {
+                    rows.push(angular(t, misalignment.dot(t), f32::NEG_INFINITY, f32::INFINITY));
+                }
+
+                // twist of b around the axis since it was joined
+                let turn = (trans_a.rotation * rest).inverse() * trans_b.rotation;
+                let mut angle = 2.0 * Vec3::new(turn.x, turn.y, turn.z).dot(axis_b).atan2(turn.w);
+                if angle > PI {
+                    angle -= 2.0 * PI;
+                } else if angle < -PI {
+                    angle += 2.0 * PI;
+                }
+                if let Some(limits) = limits {
+                    rows.extend(limit(angle, limits, |error, lo, hi| angular(world_a, error, lo, hi)));
+                }
+                if let Some(hinge_motor) = hinge_motor {
+                    rows.push(motor(angular(world_a, 0.0, 0.0, 0.0), hinge_motor));
+                }
+            }
+            JointKind::Prismatic {
+                axis,
+                limits,
+                motor: slide_motor,
+            } => {
+                let world_axis = trans_a.rotation * axis;
+                for t in tangent_basis(world_axis) {
+                    rows.push(linear(t, d.dot(t), f32::NEG_INFINITY, f32::INFINITY));
+                }
+                lock_rotation(&mut rows);
+
+                if let Some(limits) = limits {
+                    let slide = d.dot(world_axis);
+                    rows.extend(limit(slide, limits, |error, lo, hi| {
+                        linear(world_axis, error, lo, hi)
+                    }));
+                }
+                if let Some(slide_motor) = slide_motor {
+                    rows.push(motor(linear(world_axis, 0.0, 0.0, 0.0), slide_motor));
+                }
+            }
+        }
+        JointConstraints { pin, rows }
+    }
+}
+
+// One sided row that only pushes the value back inside its range, none while inside
+fn limit(
+    value: f32,
+    (min, max): (f32, f32),
+    row: impl Fn(f32, f32, f32) -> JointRow,
+) -> Option<JointRow> {
+    if value < min {
+        Some(row(value - min, 0.0, f32::INFINITY))
+    } else if value > max {
+        Some(row(value - max, f32::NEG_INFINITY, 0.0))
+    } else {
+        None
+    }
+}
+
+/// A joint pulled apart by more than its break force, the Joint has been removed
+#[derive(Debug, Clone)]
+pub struct JointBroken {
+    pub joint: Entity,
+    pub body_a: Entity,
+    pub body_b: Entity,
+}
+
+pub(crate) struct JointConstraints {
+    pub pin: Option<JointPin>,
+    pub rows: Vec<JointRow>,
+}
+
+// Keeps the anchors together, solved as one block since the three axes pull on each other
+pub(crate) struct JointPin {
+    pub ra: Vec3,
+    pub rb: Vec3,
+    /// Relative anchor velocity the solver aims for
+    pub bias: Vec3,
+}
+
+// One scalar velocity constraint, impulses along it push b by lin_b/ang_b and a by lin_a/ang_a
+pub(crate) struct JointRow {
+    pub lin_a: Vec3,
+    pub ang_a: Vec3,
+    pub lin_b: Vec3,
+    pub ang_b: Vec3,
+    /// Velocity along the row the solver aims for
+    pub bias: f32,
+    pub min_impulse: f32,
+    pub max_impulse: f32,
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::event::Events;
+
+    use super::*;
+    use crate::physics::{
+        tests::{app, step},
+        Collider, Mass, Static,
+    };
+
+    // A one kilogram box hanging 2 below a static anchor, joined by the joint make returns
+    fn hang(make: impl FnOnce(Entity, Entity) -> Joint) -> (App, Entity, Entity) {
+        let mut app = app();
+        let anchor = app
+            .world
+            .spawn()
+            .insert(Transform::from_xyz(0.0, 5.0, 0.0))
+            .insert(Collider::sphere(0.1))
+            .insert(Static)
+            .id();
+        let body = app
+            .world
+            .spawn()
+            .insert(Transform::from_xyz(0.0, 3.0, 0.0))
+            .insert(Collider::cuboid(0.25, 0.25, 0.25))
+            .insert(Mass(1.0))
+            .id();
+        let joint = app.world.spawn().insert(make(anchor, body)).id();
+        (app, body, joint)
+    }
+
+    // Fixed where the box starts, 2 below the anchor
+    fn fixed(anchor: Entity, body: Entity) -> Joint {
+        Joint::fixed(anchor, body).with_anchors(Vec3::new(0.0, -2.0, 0.0), Vec3::ZERO)
+    }
+
+    fn run(app: &mut App, steps: usize) -> Vec<JointBroken> {
+        let mut reader = app.world.resource::<Events<JointBroken>>().get_reader();
+        let mut broken = Vec::new();
+        for _ in 0..steps {
+            step(app);
+            let events = app.world.resource::<Events<JointBroken>>();
+            broken.extend(reader.iter(events).cloned());
+        }
+        broken
+    }
+
+    fn position(app: &App, body: Entity) -> Transform {
+        *app.world.get::<Transform>(body).unwrap()
+    }
+
+    #[test]
+    fn fixed_joint_holds_under_gravity() {
+        let (mut app, body, joint) = hang(fixed);
+        assert!(run(&mut app, 120).is_empty());
+        assert!(app.world.get::<Joint>(joint).is_some());
+        let trans = position(&app, body);
+        assert!(
+            trans.translation.distance(Vec3::new(0.0, 3.0, 0.0)) < 0.02,
+            "sagged to {}",
+            trans.translation
+        );
+        assert!(trans.rotation.angle_between(Quat::IDENTITY) < 0.01);
+    }
+
+    #[test]
+    fn distance_joint_holds_under_gravity() {
+        let (mut app, body, _) = hang(|a, b| Joint::distance(a, b, 2.0));
+        // swing it sideways so the joint has more than the weight to hold
+        app.world.get_mut::<Transform>(body).unwrap().translation = Vec3::new(2.0, 5.0, 0.0);
+        assert!(run(&mut app, 120).is_empty());
+        let trans = position(&app, body);
+        let length = trans.translation.distance(Vec3::new(0.0, 5.0, 0.0));
+        assert!((length - 2.0).abs() < 0.02, "stretched to {}", length);
+        assert!(trans.translation.y < 5.0);
+    }
+
+    #[test]
+    fn joint_breaks_past_its_break_force() {
+        // the box weighs about 9.8 newtons
+        let (mut app, _, joint) = hang(|a, b| fixed(a, b).with_break_force(20.0));
+        assert!(run(&mut app, 60).is_empty());
+        assert!(app.world.get::<Joint>(joint).is_some());
+
+        let (mut app, body, joint) = hang(|a, b| fixed(a, b).with_break_force(5.0));
+        let broken = run(&mut app, 60);
+        assert_eq!(broken.len(), 1);
+        assert_eq!(broken[0].joint, joint);
+        assert_eq!(broken[0].body_b, body);
+        assert!(app.world.get::<Joint>(joint).is_none());
+        // and the box falls free
+        assert!(position(&app, body).translation.y < 2.0);
+    }
+}
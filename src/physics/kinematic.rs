@@ -0,0 +1,62 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+
+use super::{AngularVelocity, LinearVelocity, PhysicsTime, PhysicsTimestep};
+
+/// Where the game last put a position based kinematic body. The game always sees its
+/// target in Transform, the steps run each frame move the body there
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct KinematicTarget {
+    pub target: Transform,
+    // where the simulation has the body, None until its first frame
+    simulated: Option<Transform>,
+}
+
+// Takes the transform the game set as the target and swaps in the simulated one for the steps
+pub fn kinematic_target_system(mut query: Query<(&mut Transform, &mut KinematicTarget)>) {
+    for (mut trans, mut kinematic) in query.iter_mut() {
+        kinematic.target = *trans;
+        match kinematic.simulated {
+            Some(simulated) => *trans = simulated,
+            None => kinematic.simulated = Some(*trans),
+        }
+    }
+}
+
+// Velocity that carries the body to its target by the end of the frame's last step,
+// this is what dynamic bodies get pushed with
+pub fn kinematic_velocity_system(
+    timestep: Res<PhysicsTimestep>,
+    pt: Res<PhysicsTime>,
+    mut query: Query<(
+        &Transform,
+        &KinematicTarget,
+        &mut LinearVelocity,
+        &mut AngularVelocity,
+    )>,
+) {
+    let span = (timestep.steps_due() + 1) as f32 * pt.time;
+    if span <= 0.0 {
+        return;
+    }
+    for (trans, kinematic, mut linear_velocity, mut angular_velocity) in query.iter_mut() {
+        let target = kinematic.target;
+        linear_velocity.0 = (target.translation - trans.translation) / span;
+
+        // shortest way round to the target rotation
+        let (axis, mut angle) = (target.rotation * trans.rotation.inverse()).to_axis_angle();
+        if angle > PI {
+            angle -= 2.0 * PI;
+        }
+        angular_velocity.0 = axis * angle / span;
+    }
+}
+
+// Keeps what the steps did and hands the game back its target
+pub fn show_kinematic_target_system(mut query: Query<(&mut Transform, &mut KinematicTarget)>) {
+    for (mut trans, mut kinematic) in query.iter_mut() {
+        kinematic.simulated = Some(*trans);
+        *trans = kinematic.target;
+    }
+}
@@ -1,13 +1,45 @@
 
+mod aabb;
+mod bvh;
 mod colliders;
+mod compound;
+mod contact;
+mod debug;
+mod events;
+mod hull;
+mod joints;
+mod kinematic;
 mod phases;
+mod query;
 mod rigid_body;
+mod sap;
+mod sleep;
+mod snapshot;
+#[cfg(test)]
+mod tests;
+mod timestep;
+mod trimesh;
 
 use bevy::prelude::*;
 use bevy_inspector_egui::{Inspectable, RegisterInspectable};
+pub use aabb::*;
+pub use bvh::*;
 pub use colliders::*;
+pub use compound::*;
+pub use contact::*;
+pub use debug::*;
+pub use events::*;
+pub use hull::*;
+pub use joints::*;
+pub use kinematic::*;
+pub use query::*;
+pub use sap::*;
+pub use sleep::*;
+pub use snapshot::*;
+pub use timestep::*;
+pub use trimesh::*;
 
-use phases::*;
+pub use phases::*;
 
 pub use self::rigid_body::*;
 
@@ -20,15 +52,46 @@ impl Default for Gravity {
         Gravity(Vec3::new(0.0, -9.8, 0.0))
     }
 }
-#[derive(Component, Inspectable, Debug, Default)]
+
+/// Whether bodies are simulated in full 3d, or kept on the XY plane like a 2d game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhysicsMode {
+    ThreeD,
+    /// Every body is locked to the XY plane, turning only around Z
+    TwoD,
+}
+
+impl Default for PhysicsMode {
+    fn default() -> Self {
+        PhysicsMode::ThreeD
+    }
+}
+
+impl PhysicsMode {
+    /// What a body with the given locks is actually held to under this mode
+    pub fn locks(&self, locked: Option<&LockedAxes>) -> LockedAxes {
+        let locked = locked.copied().unwrap_or_default();
+        match self {
+            PhysicsMode::ThreeD => locked,
+            PhysicsMode::TwoD => locked | LockedAxes::PLANAR,
+        }
+    }
+}
+
+/// World space inverse of the inertia tensor, updated every step
+#[derive(Component, Inspectable, Debug, Default, Clone, Copy)]
 pub struct InverseInertiaTensor(pub Mat3);
 
-#[derive(Default)]
+/// Seconds simulated by the step being run
+#[derive(Default, Clone, Copy)]
 pub struct PhysicsTime {
     pub time: f32,
 }
 
-pub struct PhysicsPlugin;
+#[derive(Default)]
+pub struct PhysicsPlugin {
+    pub broad_phase: BroadPhaseKind,
+}
 
 
 
@@ -37,7 +100,17 @@ impl Plugin for PhysicsPlugin {
 
         app
             .init_resource::<Gravity>()
+            .init_resource::<PhysicsMode>()
             .init_resource::<PhysicsTime>()
+            .init_resource::<PhysicsTimestep>()
+            .insert_resource(BroadPhase::new(self.broad_phase))
+            .init_resource::<Contacts>()
+            .init_resource::<SolverSettings>()
+            .init_resource::<SleepSettings>()
+            .init_resource::<CollidingPairs>()
+            .add_event::<CollisionStarted>()
+            .add_event::<CollisionEnded>()
+            .add_event::<JointBroken>()
             //.register_inspectable::<RigidBody>()
             //.register_inspectable::<Static>()
             //.register_inspectable::<LinearVelocity>()
@@ -49,11 +122,46 @@ impl Plugin for PhysicsPlugin {
             //  .register_inspectable::<CenterOfMass>()
             //  .register_inspectable::<Collider>()            
             
-            .add_system_to_stage(CoreStage::PreUpdate, update_time_system)
+            .add_stage_after(CoreStage::Update, PhysicsStage::Prepare, SystemStage::parallel())
+            .add_stage_after(
+                PhysicsStage::Prepare,
+                PhysicsStage::Step,
+                SystemStage::parallel().with_run_criteria(physics_step_criteria),
+            )
+            .add_stage_after(PhysicsStage::Step, PhysicsStage::Interpolate, SystemStage::parallel())
+            .add_system(mesh_collider_system.before(spawn_components_system))
             .add_system(spawn_components_system)
-            .add_system(update_com_world_system.after(spawn_components_system))
-            .add_system(dynamics_system.after(update_com_world_system))
-            .add_system(update_system.after(dynamics_system))
+            .add_system(mass_properties_system.after(spawn_components_system))
+            .add_system_to_stage(PhysicsStage::Prepare, compound_collider_system)
+            .add_system_to_stage(PhysicsStage::Prepare, accumulate_time_system)
+            .add_system_to_stage(PhysicsStage::Prepare, restore_transform_system)
+            .add_system_to_stage(PhysicsStage::Prepare, kinematic_target_system)
+            .add_system_to_stage(PhysicsStage::Step, record_previous_transform_system.before(update_system))
+            .add_system_to_stage(PhysicsStage::Step, update_com_world_system)
+            .add_system_to_stage(PhysicsStage::Step, update_inverse_inertia_system)
+            .add_system_to_stage(
+                PhysicsStage::Step,
+                dynamics_system
+                    .after(update_com_world_system)
+                    .after(update_inverse_inertia_system),
+            )
+            .add_system_to_stage(PhysicsStage::Step, kinematic_velocity_system)
+            .add_system_to_stage(PhysicsStage::Step, broad_phase_system.after(dynamics_system))
+            .add_system_to_stage(PhysicsStage::Step, narrow_phase_system.after(broad_phase_system))
+            .add_system_to_stage(PhysicsStage::Step, collision_events_system.after(narrow_phase_system))
+            .add_system_to_stage(
+                PhysicsStage::Step,
+                contact_solver_system
+                    .after(narrow_phase_system)
+                    .after(kinematic_velocity_system)
+                    .after(update_inverse_inertia_system),
+            )
+            .add_system_to_stage(PhysicsStage::Step, island_system.after(contact_solver_system))
+            .add_system_to_stage(PhysicsStage::Step, update_system.after(island_system))
+            .add_system_to_stage(PhysicsStage::Step, ccd_system.after(update_system))
+            .add_system_to_stage(PhysicsStage::Step, sync_broad_phase_system.after(ccd_system))
+            .add_system_to_stage(PhysicsStage::Interpolate, interpolate_transform_system)
+            .add_system_to_stage(PhysicsStage::Interpolate, show_kinematic_target_system)
             ;
     }
 }
@@ -66,47 +174,82 @@ pub fn spawn_components_system(
             Entity,
             &Collider,
             &Transform,
-            Option<&RigidBody>,
-            Option<&Mass>,
-            Option<&InvMass>,
-            Option<&LinearVelocity>,
-            Option<&AngularVelocity>,
-            Option<&Elasticity>,
-            Option<&Friction>,
-            Option<&CenterOfMass>,
-            Option<&CenterOfMassWorld>,
+            (Option<&Static>, Option<&Kinematic>),
+            (
+                Option<&RigidBody>,
+                Option<&LinearVelocity>,
+                Option<&AngularVelocity>,
+                Option<&Elasticity>,
+                Option<&Friction>,
+                Option<&TransformInterpolation>,
+            ),
+            (
+                Option<&Mass>,
+                Option<&InvMass>,
+                Option<&Density>,
+                Option<&CenterOfMass>,
+                Option<&CenterOfMassWorld>,
+                Option<&InertiaTensor>,
+                Option<&InverseInertiaTensor>,
+            ),
         ),
         (Added<Collider>),
     >,
+    hierarchy: BodyHierarchy,
 ) {
     for (
         e,
         collider,
         transform,
         //optional after this
-        rigid_body,
-        mass,
-        inv_mass,
-        linear_vel,
-        angular_vel,
-        elasticity,
-        friction,
-        center_of_mass,
-        center_of_mass_world,
+        (fixed, kinematic),
+        (rigid_body, linear_vel, angular_vel, elasticity, friction, interpolation),
+        (
+            mass,
+            inv_mass,
+            density,
+            center_of_mass,
+            center_of_mass_world,
+            inertia_tensor,
+            inverse_inertia_tensor,
+        ),
     ) in query.iter()
     {
+        // parts of a compound collider belong to the body above them
+        if owning_body(e, &hierarchy).is_some() {
+            continue;
+        }
+
         // add rigid body if not already added
         if rigid_body.is_none() {
             commands.entity(e).insert(RigidBody);
         }
 
+        // add mass, a user supplied mass wins over the shape and density
+        let mass = match mass {
+            Some(mass) => mass.0,
+            None => {
+                let density = density.map_or(Density::default().0, |d| d.0);
+                let mass = density * collider.volume();
+                commands.entity(e).insert(Mass(mass));
+                mass
+            }
+        };
+
         // add inv_mass
         if inv_mass.is_none() {
-            if let Some(mass) = mass {
-                commands.entity(e).insert(InvMass(1.0 / mass.0));
-            } else {
-                commands.entity(e).insert(InvMass(1.0));
-            }
+            let fixed = fixed.is_some() || kinematic.is_some();
+            commands.entity(e).insert(InvMass(inverse_mass(mass, fixed)));
+        }
+
+        // add inertia tensors, the world one is kept up to date every step
+        if inertia_tensor.is_none() {
+            commands
+                .entity(e)
+                .insert(InertiaTensor(collider.get_inertia_tensor() * mass));
+        }
+        if inverse_inertia_tensor.is_none() {
+            commands.entity(e).insert(InverseInertiaTensor::default());
         }
 
         // add linear velocity
@@ -141,18 +284,103 @@ pub fn spawn_components_system(
                 .entity(e)
                 .insert(CenterOfMassWorld::default());
         }
+
+        // only dynamic bodies settle and sleep
+        if fixed.is_none() && kinematic.is_none() {
+            commands.entity(e).insert(SleepState::default());
+        }
+
+        // only moving bodies need smoothing between steps, position based kinematic bodies
+        // are wherever the game last put them
+        match kinematic {
+            Some(Kinematic::PositionBased) => {
+                commands.entity(e).insert(KinematicTarget::default());
+            }
+            _ if fixed.is_none() && interpolation.is_none() => {
+                commands
+                    .entity(e)
+                    .insert(TransformInterpolation::default());
+            }
+            _ => {}
+        }
+    }
+}
+
+// Recomputes mass properties when the mass, density or shape of a body changes after spawning
+pub fn mass_properties_system(
+    mut query: Query<
+        (
+            &Collider,
+            &mut Mass,
+            &mut InvMass,
+            &mut InertiaTensor,
+            &mut CenterOfMass,
+            Option<&Density>,
+            Option<&Static>,
+            Option<&Kinematic>,
+        ),
+        Or<(Changed<Collider>, Changed<Mass>, Changed<Density>)>,
+    >,
+) {
+    for (
+        collider,
+        mut mass,
+        mut inv_mass,
+        mut inertia_tensor,
+        mut center_of_mass,
+        density,
+        fixed,
+        kinematic,
+    ) in query.iter_mut()
+    {
+        // density only drives the mass when given, otherwise the mass is left as set
+        if let Some(density) = density {
+            let from_density = density.0 * collider.volume();
+            if mass.0 != from_density {
+                mass.0 = from_density;
+            }
+        }
+
+        inv_mass.0 = inverse_mass(mass.0, fixed.is_some() || kinematic.is_some());
+        inertia_tensor.0 = collider.get_inertia_tensor() * mass.0;
+        center_of_mass.0 = collider.get_center_of_mass();
+    }
+}
+
+// Static, kinematic and massless bodies can't be moved by impulses
+fn inverse_mass(mass: f32, fixed: bool) -> f32 {
+    if fixed || mass <= 0.0 {
+        0.0
+    } else {
+        1.0 / mass
     }
 }
 
 pub fn update_com_world_system(
     mut commands: Commands,
-    mut query: Query<(&mut CenterOfMassWorld, &Transform, &CenterOfMass)>
+    mut query: Query<(&mut CenterOfMassWorld, &Transform, &CenterOfMass), Without<Sleeping>>
 ) {
     for (mut center_of_mass_world, trans, center_of_mass) in query.iter_mut() {
         center_of_mass_world.0 = trans.translation + trans.rotation * center_of_mass.0;
     }
 }
 
-fn update_time_system(time: Res<Time>, mut pt: ResMut<PhysicsTime>) {
-    pt.time = time.delta_seconds();
-}
\ No newline at end of file
+pub fn update_inverse_inertia_system(
+    mut query: Query<(
+        &mut InverseInertiaTensor,
+        &Transform,
+        &InertiaTensor,
+        Option<&Static>,
+        Option<&Kinematic>,
+    ), Without<Sleeping>>,
+) {
+    for (mut inverse_inertia, trans, inertia_tensor, fixed, kinematic) in query.iter_mut() {
+        // static and kinematic bodies, and bodies without a usable tensor, don't rotate from impulses
+        inverse_inertia.0 = if fixed.is_some() || kinematic.is_some() || inertia_tensor.0.determinant() == 0.0 {
+            Mat3::ZERO
+        } else {
+            let orientation = Mat3::from_quat(trans.rotation);
+            orientation * inertia_tensor.0.inverse() * orientation.transpose()
+        };
+    }
+}
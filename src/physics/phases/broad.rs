@@ -0,0 +1,289 @@
+use bevy::prelude::*;
+
+use crate::physics::{
+    owning_body, Aabb, BodyHierarchy, Bvh, Collider, CollisionGroups, SweepAndPrune,
+};
+
+/// Structure tracking collider bounds to find the pairs that might be touching
+pub trait BroadPhaseStrategy: Send + Sync {
+    /// Adds an entity, or moves it if already present
+    fn insert(&mut self, entity: Entity, aabb: Aabb);
+    fn remove(&mut self, entity: Entity);
+    /// Every pair of entities with overlapping bounds, lowest entity first
+    fn pairs(&mut self) -> Vec<(Entity, Entity)>;
+    /// Entities whose bounds overlap the aabb
+    fn query(&self, aabb: &Aabb) -> Vec<Entity>;
+    /// Entities whose bounds a ray passes through before max_toi
+    fn query_ray(&self, origin: Vec3, dir: Vec3, max_toi: f32) -> Vec<Entity> {
+        let end = origin + dir * max_toi;
+        self.query(&Aabb::new(origin.min(end), origin.max(end)))
+    }
+    /// Bounds the structure keeps internally, for debug drawing
+    fn node_bounds(&self) -> Vec<Aabb> {
+        Vec::new()
+    }
+    /// Copy of the whole structure, pairs come out of it in the same order as the original
+    fn clone_boxed(&self) -> Box<dyn BroadPhaseStrategy>;
+}
+
+impl BroadPhaseStrategy for Bvh {
+    fn insert(&mut self, entity: Entity, aabb: Aabb) {
+        self.update(entity, aabb);
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        Bvh::remove(self, entity);
+    }
+
+    fn pairs(&mut self) -> Vec<(Entity, Entity)> {
+        self.optimize();
+        Bvh::pairs(self)
+    }
+
+    fn query(&self, aabb: &Aabb) -> Vec<Entity> {
+        Bvh::query(self, aabb)
+    }
+
+    fn query_ray(&self, origin: Vec3, dir: Vec3, max_toi: f32) -> Vec<Entity> {
+        Bvh::query_ray(self, origin, dir, max_toi)
+    }
+
+    fn node_bounds(&self) -> Vec<Aabb> {
+        Bvh::node_bounds(self)
+    }
+
+    fn clone_boxed(&self) -> Box<dyn BroadPhaseStrategy> {
+        Box::new(self.clone())
+    }
+}
+
+impl BroadPhaseStrategy for SweepAndPrune {
+    fn insert(&mut self, entity: Entity, aabb: Aabb) {
+        SweepAndPrune::insert(self, entity, aabb);
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        SweepAndPrune::remove(self, entity);
+    }
+
+    fn pairs(&mut self) -> Vec<(Entity, Entity)> {
+        SweepAndPrune::pairs(self)
+    }
+
+    fn query(&self, aabb: &Aabb) -> Vec<Entity> {
+        SweepAndPrune::query(self, aabb)
+    }
+
+    fn clone_boxed(&self) -> Box<dyn BroadPhaseStrategy> {
+        Box::new(self.clone())
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum BroadPhaseKind {
+    /// Dynamic tree, best for big scenes spread out in 3d
+    Bvh,
+    /// Sort and sweep, simpler and quick for flat or slow moving scenes
+    SweepAndPrune,
+}
+
+impl Default for BroadPhaseKind {
+    fn default() -> Self {
+        BroadPhaseKind::Bvh
+    }
+}
+
+/// Tracks every collider, yields the pairs worth handing to the narrow phase
+pub struct BroadPhase {
+    pub strategy: Box<dyn BroadPhaseStrategy>,
+    /// Entities whose bounds overlap this frame, lowest entity first
+    pub pairs: Vec<(Entity, Entity)>,
+}
+
+impl BroadPhase {
+    pub fn new(kind: BroadPhaseKind) -> Self {
+        let strategy: Box<dyn BroadPhaseStrategy> = match kind {
+            BroadPhaseKind::Bvh => Box::new(Bvh::default()),
+            BroadPhaseKind::SweepAndPrune => Box::new(SweepAndPrune::default()),
+        };
+        Self {
+            strategy,
+            pairs: Vec::new(),
+        }
+    }
+}
+
+impl Clone for BroadPhase {
+    fn clone(&self) -> Self {
+        Self {
+            strategy: self.strategy.clone_boxed(),
+            pairs: self.pairs.clone(),
+        }
+    }
+}
+
+impl Default for BroadPhase {
+    fn default() -> Self {
+        BroadPhase::new(BroadPhaseKind::default())
+    }
+}
+
+type ChangedColliders<'w, 's> = Query<
+    'w,
+    's,
+    (Entity, &'static Collider, &'static Transform),
+    Or<(Changed<Collider>, Changed<Transform>)>,
+>;
+
+// Keeps the broad phase in step with colliders that moved, changed or went away, and
+// drops the pairs whose collision groups keep them apart
+pub fn broad_phase_system(
+    mut broad_phase: ResMut<BroadPhase>,
+    query: ChangedColliders,
+    removed: RemovedComponents<Collider>,
+    hierarchy: BodyHierarchy,
+    groups: Query<&CollisionGroups>,
+) {
+    sync_colliders(&mut broad_phase, &query, &removed, &hierarchy);
+    let groups_of = |e: Entity| groups.get(e).copied().unwrap_or_default();
+    let mut pairs = broad_phase.strategy.pairs();
+    pairs.retain(|(a, b)| groups_of(*a).interacts_with(&groups_of(*b)));
+    broad_phase.pairs = pairs;
+}
+
+// Catches up with the step's movement so queries between steps see current bounds
+pub fn sync_broad_phase_system(
+    mut broad_phase: ResMut<BroadPhase>,
+    query: ChangedColliders,
+    removed: RemovedComponents<Collider>,
+    hierarchy: BodyHierarchy,
+) {
+    sync_colliders(&mut broad_phase, &query, &removed, &hierarchy);
+}
+
+fn sync_colliders(
+    broad_phase: &mut BroadPhase,
+    query: &ChangedColliders,
+    removed: &RemovedComponents<Collider>,
+    hierarchy: &BodyHierarchy,
+) {
+    // in entity order, the shape of the tree depends on it and query order changes whenever
+    // components are added or removed
+    let mut removed = removed.iter().collect::<Vec<_>>();
+    removed.sort_unstable();
+    for e in removed {
+        broad_phase.strategy.remove(e);
+    }
+    let mut changed = query.iter().collect::<Vec<_>>();
+    changed.sort_unstable_by_key(|(e, ..)| *e);
+    for (e, collider, trans) in changed {
+        // parts of compound colliders are tested through their body
+        if owning_body(e, hierarchy).is_some() {
+            broad_phase.strategy.remove(e);
+        } else {
+            broad_phase.strategy.insert(e, collider.aabb(trans));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    fn random_aabb(rng: &mut StdRng) -> Aabb {
+        let center = Vec3::new(
+            rng.gen_range(-20.0..20.0),
+            rng.gen_range(-20.0..20.0),
+            rng.gen_range(-2.0..2.0),
+        );
+        let half = Vec3::new(
+            rng.gen_range(0.1..2.0),
+            rng.gen_range(0.1..2.0),
+            rng.gen_range(0.1..2.0),
+        );
+        Aabb::from_center(center, half)
+    }
+
+    fn sorted(mut pairs: Vec<(Entity, Entity)>) -> Vec<(Entity, Entity)> {
+        pairs.sort();
+        pairs
+    }
+
+    #[test]
+    fn strategies_agree() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut world = World::new();
+        let entities = (0..200).map(|_| world.spawn().id()).collect::<Vec<_>>();
+        let mut bounds: Vec<Option<Aabb>> = vec![None; entities.len()];
+
+        let mut strategies: Vec<Box<dyn BroadPhaseStrategy>> = vec![
+            Box::new(Bvh::default()),
+            Box::new(SweepAndPrune::default()),
+        ];
+        for _ in 0..20 {
+            // a mix of spawning, despawning and small moves
+            for _ in 0..50 {
+                let i = rng.gen_range(0..entities.len());
+                let aabb = match (rng.gen_range(0..4), bounds[i]) {
+                    (0, _) => None,
+                    (1, _) | (_, None) => Some(random_aabb(&mut rng)),
+                    (_, Some(b)) => {
+                        let d = Vec3::new(rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5), 0.0);
+                        Some(Aabb::new(b.min + d, b.max + d))
+                    }
+                };
+                for strategy in strategies.iter_mut() {
+                    match aabb {
+                        Some(aabb) => strategy.insert(entities[i], aabb),
+                        None => strategy.remove(entities[i]),
+                    }
+                }
+                bounds[i] = aabb;
+            }
+
+            let mut expected = Vec::new();
+            for i in 0..entities.len() {
+                for j in i + 1..entities.len() {
+                    if let (Some(a), Some(b)) = (bounds[i], bounds[j]) {
+                        if a.intersects(&b) {
+                            let (x, y) = (entities[i], entities[j]);
+                            expected.push(if x < y { (x, y) } else { (y, x) });
+                        }
+                    }
+                }
+            }
+            let expected = sorted(expected);
+
+            let area = Aabb::from_center(Vec3::ZERO, Vec3::splat(5.0));
+            let mut inside = (0..entities.len())
+                .filter(|i| bounds[*i].map_or(false, |b| b.intersects(&area)))
+                .map(|i| entities[i])
+                .collect::<Vec<_>>();
+            inside.sort();
+
+            for strategy in strategies.iter_mut() {
+                assert_eq!(sorted(strategy.pairs()), expected);
+                let mut hits = strategy.query(&area);
+                hits.sort();
+                assert_eq!(hits, inside);
+            }
+        }
+    }
+
+    #[test]
+    fn bvh_build_matches_incremental() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut world = World::new();
+        let items = (0..500)
+            .map(|_| (world.spawn().id(), random_aabb(&mut rng)))
+            .collect::<Vec<_>>();
+
+        let built = Bvh::build(0.1, items.iter().copied());
+        let mut incremental = Bvh::default();
+        for (e, aabb) in items.iter() {
+            incremental.insert(*e, *aabb);
+        }
+        assert_eq!(sorted(built.pairs()), sorted(incremental.pairs()));
+    }
+}
@@ -0,0 +1,127 @@
+use std::cmp::Ordering;
+
+use bevy::prelude::*;
+
+use crate::physics::{
+    BroadPhase, Ccd, Collider, CollisionGroups, Elasticity, LinearVelocity, Sensor, Sleeping,
+    SolverSettings, TransformInterpolation,
+};
+
+// Sweeps Ccd bodies from where they started the step to where they were integrated to.
+// On a hit the body is put back at the time of impact, just inside the surface so the
+// narrow phase still sees the contact next step, and bounced off it. The rest of the
+// step's motion is dropped. Whatever was hit is treated as immovable here, the solver
+// deals with the contact properly next step. Other Ccd bodies and sensors are left to the
+// discrete step
+pub fn ccd_system(
+    broad_phase: Res<BroadPhase>,
+    settings: Res<SolverSettings>,
+    mut movers: Query<
+        (
+            Entity,
+            &Collider,
+            &mut Transform,
+            &TransformInterpolation,
+            &mut LinearVelocity,
+            &Elasticity,
+            Option<&CollisionGroups>,
+        ),
+        (With<Ccd>, Without<Sleeping>, Without<Sensor>),
+    >,
+    others: Query<
+        (
+            &Collider,
+            &Transform,
+            &Elasticity,
+            Option<&LinearVelocity>,
+            Option<&CollisionGroups>,
+        ),
+        (Without<Ccd>, Without<Sensor>),
+    >,
+) {
+    for (e, collider, mut trans, interpolation, mut velocity, elasticity, groups) in
+        movers.iter_mut()
+    {
+        let groups = groups.copied().unwrap_or_default();
+        let start = interpolation.previous.translation;
+        let motion = trans.translation - start;
+        let distance = motion.length();
+        // slow enough that the discrete step can't miss anything
+        if distance < 0.5 * collider.min_width() {
+            continue;
+        }
+        let dir = motion / distance;
+
+        let from = Transform {
+            translation: start,
+            ..*trans
+        };
+        let swept = collider.aabb(&from).union(&collider.aabb(&trans));
+        let hit = broad_phase
+            .strategy
+            .query(&swept)
+            .into_iter()
+            .filter(|other| *other != e)
+            .filter_map(|other| {
+                let (other_collider, other_trans, other_elasticity, other_velocity, other_groups) =
+                    others.get(other).ok()?;
+                if !groups.interacts_with(&other_groups.copied().unwrap_or_default()) {
+                    return None;
+                }
+                let (toi, _, normal) =
+                    collider.cast_shape(&from, dir, distance, other_collider, other_trans)?;
+                // only surfaces it is closing on, resting contacts are the solver's
+                let relative = velocity.0 - other_velocity.map_or(Vec3::ZERO, |v| v.0);
+                let closing = relative.dot(normal);
+                (closing < 0.0).then(|| (toi, normal, closing, other_elasticity.0))
+            })
+            .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+
+        if let Some((toi, normal, closing, other_elasticity)) = hit {
+            trans.translation = start + dir * (toi + settings.slop).min(distance);
+            let restitution = elasticity.0 * other_elasticity;
+            velocity.0 -= (1.0 + restitution) * closing * normal;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::physics::{
+        tests::{app, step},
+        Gravity, Static,
+    };
+
+    // A small ball fired at a thin wall fast enough to cross it in one step
+    fn fire(ccd: bool) -> f32 {
+        let mut app = app();
+        app.insert_resource(Gravity(Vec3::ZERO));
+        app.world
+            .spawn()
+            .insert(Transform::from_xyz(5.0, 0.0, 0.0))
+            .insert(Collider::cuboid(0.05, 2.0, 2.0))
+            .insert(Static);
+        let mut ball = app.world.spawn();
+        ball.insert(Transform::default())
+            .insert(Collider::sphere(0.1))
+            .insert(LinearVelocity(Vec3::X * 600.0));
+        if ccd {
+            ball.insert(Ccd);
+        }
+        let ball = ball.id();
+
+        for _ in 0..10 {
+            step(&mut app);
+        }
+        app.world.get::<Transform>(ball).unwrap().translation.x
+    }
+
+    #[test]
+    fn fast_body_stops_at_thin_wall() {
+        assert!(fire(false) > 5.0, "the wall is thin enough to tunnel through");
+        // still on the near side of the wall's face
+        let x = fire(true);
+        assert!(x < 4.95, "passed the wall to {}", x);
+    }
+}
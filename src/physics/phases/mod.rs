@@ -1,24 +1,74 @@
+mod broad;
+mod ccd;
 mod dynamics;
+mod narrow;
+mod solver;
 
+pub use broad::*;
+pub use ccd::*;
 pub use dynamics::*;
+pub use narrow::*;
+pub use solver::*;
 
 use bevy::prelude::*;
 
 use super::{
     AngularVelocity, CenterOfMassWorld, InertiaTensor, LinearVelocity, PhysicsTime, RigidBody,
-    Static, Mass, InvMass, Gravity,
+    Static, Mass, InvMass, Gravity, Kinematic, Sleeping, LockedAxes, PhysicsMode,
+    ExternalForce, ExternalTorque, Damping, GravityScale, InverseInertiaTensor,
 };
 
-pub fn dynamics_system(mut query: Query<(&mut LinearVelocity, &Mass, &InvMass), Without<Static>>, gravity: Res<Gravity>, pt: Res<PhysicsTime>) {
-    for (mut linear_velocity, mass, inv_mass) in query.iter_mut(){
+// Adds gravity and external forces to the velocities of dynamic bodies, then damps them
+pub fn dynamics_system(
+    mut query: Query<
+        (
+            (&mut LinearVelocity, &mut AngularVelocity),
+            (&Mass, &InvMass, &InverseInertiaTensor),
+            (
+                Option<&ExternalForce>,
+                Option<&ExternalTorque>,
+                Option<&Damping>,
+                Option<&GravityScale>,
+            ),
+            Option<&LockedAxes>,
+        ),
+        (Without<Static>, Without<Kinematic>, Without<Sleeping>),
+    >,
+    gravity: Res<Gravity>,
+    mode: Res<PhysicsMode>,
+    pt: Res<PhysicsTime>,
+) {
+    for (
+        (mut linear_velocity, mut angular_velocity),
+        (mass, inv_mass, inv_inertia),
+        (force, torque, damping, gravity_scale),
+        locked,
+    ) in query.iter_mut()
+    {
+        // Apply Gravity, it needs to be an impluse
+        let gravity_scale = gravity_scale.map_or(1.0, |s| s.0);
+        let gravey_impluse = gravity.0 * gravity_scale * mass.0 * pt.time;
+
+        // a body without mass has no inverse mass, nothing moves it
+        linear_velocity.0 += gravey_impluse * inv_mass.0;
 
-        // Apply Gravity, it needs to be an impluse        
-        let gravey_impluse = gravity.0 * mass.0 * pt.time;
+        // forces and torques are held until changed, so they act over every step
+        if let Some(force) = force {
+            linear_velocity.0 += force.0 * inv_mass.0 * pt.time;
+        }
+        if let Some(torque) = torque {
+            angular_velocity.0 += inv_inertia.0 * torque.0 * pt.time;
+        }
 
-        // since rb is not static, inv mass shouldnt be 0 or less
-        assert!(inv_mass.0 > 0.0);
+        // stable for any damping or step, unlike taking off damping * dt
+        if let Some(damping) = damping {
+            linear_velocity.0 /= 1.0 + damping.linear * pt.time;
+            angular_velocity.0 /= 1.0 + damping.angular * pt.time;
+        }
 
-        linear_velocity.0 += gravey_impluse * inv_mass.0;
+        let locks = mode.locks(locked);
+        linear_velocity.0 *= locks.linear_factor();
+        angular_velocity.0 *= locks.angular_factor();
     }
 }
 
@@ -30,19 +80,30 @@ pub fn update_system(
             &mut AngularVelocity,
             &CenterOfMassWorld,
             &InertiaTensor,
+            &InverseInertiaTensor,
+            Option<&LockedAxes>,
         ),
-        Without<Static>,
+        (Without<Static>, Without<Sleeping>),
     >,
+    mode: Res<PhysicsMode>,
     pt: Res<PhysicsTime>,
 ) {
-    for (mut t, mut lin_vel, mut ang_vel, com_w, inertia_tensor) in query.iter_mut() {
+    for (mut t, mut lin_vel, mut ang_vel, com_w, inertia_tensor, inv_inertia, locked) in
+        query.iter_mut()
+    {
+        // whatever set the velocities, locked axes stay put
+        let locks = mode.locks(locked);
+        lin_vel.0 *= locks.linear_factor();
+        ang_vel.0 *= locks.angular_factor();
         RigidBody::update(
             &mut t,
             &mut lin_vel,
             &mut ang_vel,
             com_w,
             inertia_tensor,
+            inv_inertia,
             pt.time,
         );
+        ang_vel.0 *= locks.angular_factor();
     }
 }
@@ -0,0 +1,72 @@
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::physics::{
+    BroadPhase, Collider, Contact, Contacts, Joint, Kinematic, Sensor, Sleeping, Static,
+};
+
+// Tests the broad phase pairs and records the ones touching
+pub fn narrow_phase_system(
+    broad_phase: Res<BroadPhase>,
+    query: Query<(
+        &Collider,
+        &Transform,
+        Option<&Static>,
+        Option<&Kinematic>,
+        Option<&Sleeping>,
+        Option<&Sensor>,
+    )>,
+    joints: Query<&Joint>,
+    mut contacts: ResMut<Contacts>,
+) {
+    contacts.0.clear();
+
+    // jointed bodies pass through each other unless asked not to
+    let joined = joints
+        .iter()
+        .filter(|j| !j.collide_connected)
+        .map(|j| (j.body_a.min(j.body_b), j.body_a.max(j.body_b)))
+        .collect::<HashSet<_>>();
+
+    for (entity_a, entity_b) in broad_phase.pairs.iter() {
+        if joined.contains(&(*entity_a, *entity_b)) {
+            continue;
+        }
+
+        let (
+            (collider_a, trans_a, static_a, kinematic_a, sleeping_a, sensor_a),
+            (collider_b, trans_b, static_b, kinematic_b, sleeping_b, sensor_b),
+        ) = match (query.get(*entity_a), query.get(*entity_b)) {
+            (Ok(a), Ok(b)) => (a, b),
+            _ => continue,
+        };
+
+        // nothing to resolve between two bodies contacts can't move
+        let fixed_a = static_a.is_some() || kinematic_a.is_some();
+        let fixed_b = static_b.is_some() || kinematic_b.is_some();
+        if fixed_a && fixed_b {
+            continue;
+        }
+        // nor between bodies that are settled, moving kinematic bodies still need testing
+        let resting_a = static_a.is_some() || sleeping_a.is_some();
+        let resting_b = static_b.is_some() || sleeping_b.is_some();
+        if resting_a && resting_b {
+            continue;
+        }
+
+        // compound bodies get a contact for each pair of parts touching
+        for (part_a, collider_a, trans_a) in collider_a.parts(trans_a) {
+            for (part_b, collider_b, trans_b) in collider_b.parts(trans_b) {
+                if let Some(manifold) = collider_a.contact(&trans_a, collider_b, &trans_b) {
+                    contacts.0.push(Contact {
+                        entity_a: *entity_a,
+                        entity_b: *entity_b,
+                        collider_a: part_a.unwrap_or(*entity_a),
+                        collider_b: part_b.unwrap_or(*entity_b),
+                        manifold,
+                        sensor: sensor_a.is_some() || sensor_b.is_some(),
+                    });
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,413 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use crate::physics::{
+    AngularVelocity, CenterOfMassWorld, Contacts, Elasticity, Friction, Gravity, GravityScale,
+    InvMass, InverseInertiaTensor, Joint, JointBroken, JointPin, JointRow, Kinematic,
+    LinearVelocity, LockedAxes, PhysicsMode, PhysicsTime, Sleeping, Static,
+};
+
+pub struct SolverSettings {
+    /// Velocity iterations over all contacts per step
+    pub iterations: usize,
+    /// Fraction of the penetration removed each step, Baumgarte stabilisation
+    pub baumgarte: f32,
+    /// Penetration allowed before pushing bodies apart, stops resting contacts jittering
+    pub slop: f32,
+    /// Closing speeds below this don't bounce, so resting bodies settle
+    pub restitution_threshold: f32,
+}
+
+impl Default for SolverSettings {
+    fn default() -> Self {
+        Self {
+            iterations: 8,
+            baumgarte: 0.2,
+            slop: 0.005,
+            restitution_threshold: 0.2,
+        }
+    }
+}
+
+// Working copy of a body while the solver runs
+struct SolverBody {
+    entity: Entity,
+    transform: Transform,
+    // per axis, locked axes have none
+    inv_mass: Vec3,
+    inv_inertia: Mat3,
+    center_of_mass: Vec3,
+    linear_velocity: Vec3,
+    angular_velocity: Vec3,
+    // velocity gravity added this step, bounces leave it out so they don't gain energy
+    fallen: Vec3,
+    elasticity: f32,
+    friction: f32,
+}
+
+impl SolverBody {
+    // Nothing can move it, static or kinematic or fully locked
+    fn is_fixed(&self) -> bool {
+        self.inv_mass == Vec3::ZERO && self.inv_inertia == Mat3::ZERO
+    }
+
+    fn velocity_at(&self, r: Vec3) -> Vec3 {
+        self.linear_velocity + self.angular_velocity.cross(r)
+    }
+
+    fn apply_impulse(&mut self, r: Vec3, impulse: Vec3) {
+        self.linear_velocity += impulse * self.inv_mass;
+        self.angular_velocity += self.inv_inertia * r.cross(impulse);
+    }
+
+    // Inverse of the mass felt by an impulse along dir at r
+    fn inv_effective_mass(&self, r: Vec3, dir: Vec3) -> f32 {
+        dir.dot(self.inv_mass * dir) + (self.inv_inertia * r.cross(dir)).cross(r).dot(dir)
+    }
+
+    // Speed along one side of a joint row
+    fn row_velocity(&self, lin: Vec3, ang: Vec3) -> f32 {
+        lin.dot(self.linear_velocity) + ang.dot(self.angular_velocity)
+    }
+
+    fn apply_row_impulse(&mut self, lin: Vec3, ang: Vec3, impulse: f32) {
+        self.linear_velocity += lin * impulse * self.inv_mass;
+        self.angular_velocity += self.inv_inertia * ang * impulse;
+    }
+
+    fn row_inv_mass(&self, lin: Vec3, ang: Vec3) -> f32 {
+        (lin * lin).dot(self.inv_mass) + ang.dot(self.inv_inertia * ang)
+    }
+
+    // Inverse mass felt by an impulse in any direction at r
+    fn point_inv_mass(&self, r: Vec3) -> Mat3 {
+        let skew = skew(r);
+        Mat3::from_diagonal(self.inv_mass) - skew * self.inv_inertia * skew
+    }
+}
+
+struct ContactConstraint {
+    a: usize,
+    b: usize,
+    ra: Vec3,
+    rb: Vec3,
+    normal: Vec3,
+    tangents: [Vec3; 2],
+    normal_mass: f32,
+    tangent_mass: [f32; 2],
+    friction: f32,
+    bias: f32,
+    normal_impulse: f32,
+    tangent_impulse: [f32; 2],
+}
+
+struct JointPinConstraint {
+    joint: usize,
+    a: usize,
+    b: usize,
+    pin: JointPin,
+    mass: Mat3,
+    impulse: Vec3,
+}
+
+struct JointConstraint {
+    // index into the solved joints
+    joint: usize,
+    a: usize,
+    b: usize,
+    row: JointRow,
+    mass: f32,
+    impulse: f32,
+}
+
+// Sequential impulse solver, resolves contacts found by the narrow phase together with joints
+pub fn contact_solver_system(
+    mut commands: Commands,
+    contacts: Res<Contacts>,
+    settings: Res<SolverSettings>,
+    pt: Res<PhysicsTime>,
+    mode: Res<PhysicsMode>,
+    gravity: Res<Gravity>,
+    mut joints: Query<(Entity, &mut Joint)>,
+    mut joint_broken: EventWriter<JointBroken>,
+    mut query: Query<(
+        &Transform,
+        &mut LinearVelocity,
+        &mut AngularVelocity,
+        &CenterOfMassWorld,
+        &InvMass,
+        &InverseInertiaTensor,
+        &Elasticity,
+        &Friction,
+        Option<&Static>,
+        Option<&Kinematic>,
+        Option<&LockedAxes>,
+        (Option<&GravityScale>, Option<&Sleeping>),
+    )>,
+) {
+    if pt.time <= 0.0 || (contacts.0.is_empty() && joints.is_empty()) {
+        return;
+    }
+
+    let mut bodies = Vec::new();
+    let mut indices = HashMap::default();
+    let mut index_of = |e: Entity, bodies: &mut Vec<SolverBody>| -> Option<usize> {
+        if let Some(i) = indices.get(&e) {
+            return Some(*i);
+        }
+        let (
+            trans,
+            lin_vel,
+            ang_vel,
+            com_world,
+            inv_mass,
+            inv_inertia,
+            elasticity,
+            friction,
+            fixed,
+            kinematic,
+            locked,
+            (gravity_scale, sleeping),
+        ) = query.get(e).ok()?;
+
+        // static and kinematic bodies have infinite mass, as does a body along its locked axes
+        let (inv_mass, inv_inertia, fallen) = if fixed.is_some() || kinematic.is_some() {
+            (Vec3::ZERO, Mat3::ZERO, Vec3::ZERO)
+        } else {
+            let locks = mode.locks(locked);
+            let angular = Mat3::from_diagonal(locks.angular_factor());
+            // as given by dynamics_system, which skips sleeping and massless bodies
+            let falls = sleeping.is_none() && inv_mass.0 > 0.0;
+            let fallen = if falls {
+                gravity.0 * gravity_scale.map_or(1.0, |s| s.0) * pt.time * locks.linear_factor()
+            } else {
+                Vec3::ZERO
+            };
+            (
+                inv_mass.0 * locks.linear_factor(),
+                angular * inv_inertia.0 * angular,
+                fallen,
+            )
+        };
+        bodies.push(SolverBody {
+            entity: e,
+            transform: *trans,
+            inv_mass,
+            inv_inertia,
+            center_of_mass: com_world.0,
+            linear_velocity: lin_vel.0,
+            angular_velocity: ang_vel.0,
+            fallen,
+            elasticity: elasticity.0,
+            friction: friction.0,
+        });
+        indices.insert(e, bodies.len() - 1);
+        Some(bodies.len() - 1)
+    };
+
+    let mut constraints = Vec::new();
+    for contact in contacts.0.iter().filter(|c| !c.sensor) {
+        let (a, b) = match (
+            index_of(contact.entity_a, &mut bodies),
+            index_of(contact.entity_b, &mut bodies),
+        ) {
+            (Some(a), Some(b)) => (a, b),
+            _ => continue,
+        };
+        if bodies[a].is_fixed() && bodies[b].is_fixed() {
+            continue;
+        }
+        let elasticity = bodies[a].elasticity * bodies[b].elasticity;
+        let friction = bodies[a].friction * bodies[b].friction;
+
+        let normal = contact.manifold.normal;
+        let tangents = tangent_basis(normal);
+        for point in contact.manifold.points.iter() {
+            let (body_a, body_b) = (&bodies[a], &bodies[b]);
+            let ra = point.point_a - body_a.center_of_mass;
+            let rb = point.point_b - body_b.center_of_mass;
+
+            // bounce off the speed the bodies met at, and push out any penetration past the
+            // slop. Gravity from this step is left out of the bounce, the contact cancels it
+            let closing = (body_b.velocity_at(rb) - body_a.velocity_at(ra)).dot(normal);
+            let met = closing - (body_b.fallen - body_a.fallen).dot(normal);
+            let restitution = if met < -settings.restitution_threshold {
+                -elasticity * met
+            } else {
+                0.0
+            };
+            let correction = settings.baumgarte / pt.time * (point.depth - settings.slop).max(0.0);
+
+            let inv_mass = |dir: Vec3| {
+                let k = body_a.inv_effective_mass(ra, dir) + body_b.inv_effective_mass(rb, dir);
+                if k > 0.0 {
+                    1.0 / k
+                } else {
+                    0.0
+                }
+            };
+            constraints.push(ContactConstraint {
+                a,
+                b,
+                ra,
+                rb,
+                normal,
+                tangents,
+                normal_mass: inv_mass(normal),
+                tangent_mass: [inv_mass(tangents[0]), inv_mass(tangents[1])],
+                friction,
+                bias: restitution.max(correction),
+                normal_impulse: 0.0,
+                tangent_impulse: [0.0; 2],
+            });
+        }
+    }
+
+    let bias_factor = settings.baumgarte / pt.time;
+    let mut solved_joints = Vec::new();
+    let mut joint_pins = Vec::new();
+    let mut joint_constraints = Vec::new();
+    // solved in entity order so the results don't depend on how the joints are stored
+    let mut joints = joints.iter_mut().collect::<Vec<_>>();
+    joints.sort_unstable_by_key(|(e, _)| *e);
+    for (joint_entity, mut joint) in joints {
+        let (a, b) = match (
+            index_of(joint.body_a, &mut bodies),
+            index_of(joint.body_b, &mut bodies),
+        ) {
+            (Some(a), Some(b)) => (a, b),
+            _ => continue,
+        };
+        let (body_a, body_b) = (&bodies[a], &bodies[b]);
+        // only touched the once, so changes to a joint can be told apart
+        if !joint.has_rest() {
+            joint.capture_rest(&body_a.transform, &body_b.transform);
+        }
+        let constraints = joint.constraints(
+            &body_a.transform,
+            body_a.center_of_mass,
+            &body_b.transform,
+            body_b.center_of_mass,
+            bias_factor,
+            pt.time,
+        );
+        if let Some(pin) = constraints.pin {
+            let mut k = body_a.point_inv_mass(pin.ra) + body_b.point_inv_mass(pin.rb);
+            // an axis neither body can move along has an empty row, keep it out of the inverse
+            for i in 0..3 {
+                if k.col(i)[i].abs() <= f32::EPSILON {
+                    k.col_mut(i)[i] = 1.0;
+                }
+            }
+            joint_pins.push(JointPinConstraint {
+                joint: solved_joints.len(),
+                a,
+                b,
+                pin,
+                mass: if k.determinant().abs() > f32::EPSILON { k.inverse() } else { Mat3::ZERO },
+                impulse: Vec3::ZERO,
+            });
+        }
+        for row in constraints.rows {
+            let k = body_a.row_inv_mass(row.lin_a, row.ang_a) + body_b.row_inv_mass(row.lin_b, row.ang_b);
+            joint_constraints.push(JointConstraint {
+                joint: solved_joints.len(),
+                a,
+                b,
+                mass: if k > 0.0 { 1.0 / k } else { 0.0 },
+                row,
+                impulse: 0.0,
+            });
+        }
+        solved_joints.push((joint_entity, joint.body_a, joint.body_b, joint.break_force));
+    }
+
+    for _ in 0..settings.iterations {
+        for c in joint_pins.iter_mut() {
+            let relative = bodies[c.b].velocity_at(c.pin.rb) - bodies[c.a].velocity_at(c.pin.ra);
+            let impulse = c.mass * (c.pin.bias - relative);
+            c.impulse += impulse;
+            bodies[c.a].apply_impulse(c.pin.ra, -impulse);
+            bodies[c.b].apply_impulse(c.pin.rb, impulse);
+        }
+        for c in joint_constraints.iter_mut() {
+            let row = &c.row;
+            let velocity = bodies[c.a].row_velocity(row.lin_a, row.ang_a)
+                + bodies[c.b].row_velocity(row.lin_b, row.ang_b);
+            let lambda = (row.bias - velocity) * c.mass;
+            let total = (c.impulse + lambda).clamp(row.min_impulse, row.max_impulse);
+            let applied = total - c.impulse;
+            c.impulse = total;
+            bodies[c.a].apply_row_impulse(row.lin_a, row.ang_a, applied);
+            bodies[c.b].apply_row_impulse(row.lin_b, row.ang_b, applied);
+        }
+
+        for c in constraints.iter_mut() {
+            // friction, Coulomb cone limited by the current normal impulse
+            let max_friction = c.friction * c.normal_impulse;
+            for i in 0..2 {
+                let relative = bodies[c.b].velocity_at(c.rb) - bodies[c.a].velocity_at(c.ra);
+                let lambda = -relative.dot(c.tangents[i]) * c.tangent_mass[i];
+                let total = (c.tangent_impulse[i] + lambda).clamp(-max_friction, max_friction);
+                let applied = total - c.tangent_impulse[i];
+                c.tangent_impulse[i] = total;
+                apply_pair(&mut bodies, c, c.tangents[i] * applied);
+            }
+
+            // normal, bodies can only be pushed apart
+            let relative = bodies[c.b].velocity_at(c.rb) - bodies[c.a].velocity_at(c.ra);
+            let lambda = (c.bias - relative.dot(c.normal)) * c.normal_mass;
+            let total = (c.normal_impulse + lambda).max(0.0);
+            let applied = total - c.normal_impulse;
+            c.normal_impulse = total;
+            apply_pair(&mut bodies, c, c.normal * applied);
+        }
+    }
+
+    // joints pulled harder than they can take let go
+    let mut pull = vec![Vec3::ZERO; solved_joints.len()];
+    for c in joint_pins.iter() {
+        pull[c.joint] += c.impulse;
+    }
+    for c in joint_constraints.iter() {
+        pull[c.joint] += c.row.lin_b * c.impulse;
+    }
+    for ((joint, body_a, body_b, break_force), pull) in solved_joints.into_iter().zip(pull) {
+        if break_force.map_or(false, |f| pull.length() / pt.time > f) {
+            commands.entity(joint).remove::<Joint>();
+            joint_broken.send(JointBroken {
+                joint,
+                body_a,
+                body_b,
+            });
+        }
+    }
+
+    for body in bodies.iter().filter(|b| !b.is_fixed()) {
+        if let Ok((_, mut lin_vel, mut ang_vel, ..)) = query.get_mut(body.entity) {
+            lin_vel.0 = body.linear_velocity;
+            ang_vel.0 = body.angular_velocity;
+        }
+    }
+}
+
+// Impulse pushes b along it and a against it
+fn apply_pair(bodies: &mut [SolverBody], c: &ContactConstraint, impulse: Vec3) {
+    bodies[c.a].apply_impulse(c.ra, -impulse);
+    bodies[c.b].apply_impulse(c.rb, impulse);
+}
+
+// Matrix doing the cross product v x
+fn skew(v: Vec3) -> Mat3 {
+    Mat3::from_cols(
+        Vec3::new(0.0, v.z, -v.y),
+        Vec3::new(-v.z, 0.0, v.x),
+        Vec3::new(v.y, -v.x, 0.0),
+    )
+}
+
+// Two directions perpendicular to the normal and each other
+pub(crate) fn tangent_basis(normal: Vec3) -> [Vec3; 2] {
+    let helper = if normal.x.abs() < 0.57 { Vec3::X } else { Vec3::Y };
+    let t1 = normal.cross(helper).normalize();
+    [t1, normal.cross(t1)]
+}
@@ -0,0 +1,784 @@
+use std::cmp::Ordering;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use super::{Aabb, BroadPhase, Collider, CollisionGroups, Sensor};
+
+// Bisection steps refining a shape cast hit, enough for well under a millimetre on most casts
+const CAST_REFINE_STEPS: usize = 16;
+
+/// Where a ray met a collider
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit {
+    pub entity: Entity,
+    /// Distance along the ray, 0 when it started inside the collider
+    pub toi: f32,
+    /// World space point on the collider surface
+    pub point: Vec3,
+    /// World space surface normal at the point, facing back along the ray
+    pub normal: Vec3,
+}
+
+/// Where a moving shape first touched a collider
+#[derive(Debug, Clone, Copy)]
+pub struct ShapeHit {
+    pub entity: Entity,
+    /// Distance travelled before touching, 0 when it started overlapping
+    pub toi: f32,
+    /// World space point on the surface of the collider that was hit
+    pub point: Vec3,
+    /// World space surface normal of the collider that was hit, facing the moving shape
+    pub normal: Vec3,
+}
+
+/// Narrows down which colliders a query can hit
+#[derive(Default)]
+pub struct QueryFilter<'a> {
+    pub exclude: Vec<Entity>,
+    /// Only entities this returns true for are considered
+    pub predicate: Option<&'a dyn Fn(Entity) -> bool>,
+    /// Groups the query acts as, colliders it doesn't interact with are skipped
+    pub groups: Option<CollisionGroups>,
+    pub exclude_sensors: bool,
+}
+
+impl<'a> QueryFilter<'a> {
+    pub fn exclude(mut self, entity: Entity) -> Self {
+        self.exclude.push(entity);
+        self
+    }
+
+    pub fn predicate(mut self, predicate: &'a dyn Fn(Entity) -> bool) -> Self {
+        self.predicate = Some(predicate);
+        self
+    }
+
+    pub fn groups(mut self, groups: CollisionGroups) -> Self {
+        self.groups = Some(groups);
+        self
+    }
+
+    pub fn exclude_sensors(mut self) -> Self {
+        self.exclude_sensors = true;
+        self
+    }
+
+    pub fn test(&self, entity: Entity) -> bool {
+        !self.exclude.contains(&entity) && self.predicate.map_or(true, |p| p(entity))
+    }
+}
+
+impl Collider {
+    /// First hit of a ray against the collider, dir must be normalized
+    pub fn cast_ray(
+        &self,
+        trans: &Transform,
+        origin: Vec3,
+        dir: Vec3,
+        max_toi: f32,
+    ) -> Option<(f32, Vec3)> {
+        match self {
+            Collider::Sphere { radius } => {
+                ray_sphere(origin, dir, trans.translation, *radius, max_toi)
+            }
+            Collider::Cuboid { size } => {
+                // slab test in the box's own frame
+                let inv = trans.rotation.inverse();
+                let local_origin = inv * (origin - trans.translation);
+                let local_dir = inv * dir;
+                let (enter, _) = Aabb::from_center(Vec3::ZERO, *size).ray_interval(
+                    local_origin,
+                    local_dir,
+                    max_toi,
+                )?;
+                if enter <= 0.0 {
+                    return Some((0.0, -dir));
+                }
+                // the face hit is the one the point sits furthest out on
+                let p = (local_origin + local_dir * enter) / *size;
+                let axis = if p.x.abs() >= p.y.abs() && p.x.abs() >= p.z.abs() {
+                    0
+                } else if p.y.abs() >= p.z.abs() {
+                    1
+                } else {
+                    2
+                };
+                let mut normal = Vec3::ZERO;
+                normal[axis] = p[axis].signum();
+                Some((enter, trans.rotation * normal))
+            }
+            Collider::Capsule {
+                half_height,
+                radius,
+            } => {
+                let up = trans.rotation * Vec3::Y * *half_height;
+                let (a, b) = (trans.translation - up, trans.translation + up);
+                ray_capsule(origin, dir, a, b, *radius, max_toi)
+            }
+            Collider::Plane { normal } => {
+                let normal = trans.rotation * *normal;
+                let height = normal.dot(origin - trans.translation);
+                if height <= 0.0 {
+                    return Some((0.0, -dir));
+                }
+                let closing = normal.dot(dir);
+                if closing >= 0.0 {
+                    return None;
+                }
+                let toi = -height / closing;
+                (toi <= max_toi).then(|| (toi, normal))
+            }
+            Collider::ConvexHull(hull) => {
+                // clip the ray by every face plane, in the hull's own frame
+                let inv = trans.rotation.inverse();
+                let local_origin = inv * (origin - trans.translation);
+                let local_dir = inv * dir;
+                let (mut enter, mut exit, mut normal) = (0.0_f32, max_toi, None);
+                for face in hull.faces() {
+                    let height = face.normal.dot(local_origin) - face.offset;
+                    let closing = face.normal.dot(local_dir);
+                    if closing.abs() <= f32::EPSILON {
+                        if height > 0.0 {
+                            return None;
+                        }
+                        continue;
+                    }
+                    let toi = -height / closing;
+                    if closing < 0.0 {
+                        if toi > enter {
+                            enter = toi;
+                            normal = Some(face.normal);
+                        }
+                    } else {
+                        exit = exit.min(toi);
+                    }
+                    if enter > exit {
+                        return None;
+                    }
+                }
+                Some(normal.map_or((0.0, -dir), |n| (enter, trans.rotation * n)))
+            }
+            Collider::TriMesh(mesh) => {
+                let inv = trans.rotation.inverse();
+                let local_origin = inv * (origin - trans.translation);
+                let local_dir = inv * dir;
+                mesh.query_ray(local_origin, local_dir, max_toi)
+                    .filter_map(|i| {
+                        ray_triangle(local_origin, local_dir, mesh.triangle(i), max_toi)
+                    })
+                    .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal))
+                    .map(|(toi, normal)| (toi, trans.rotation * normal))
+            }
+            Collider::Compound(_) => self
+                .parts(trans)
+                .filter_map(|(_, collider, part_trans)| {
+                    collider.cast_ray(&part_trans, origin, dir, max_toi)
+                })
+                .min_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal)),
+        }
+    }
+
+    /// Whether a world space point is inside the collider
+    pub fn contains_point(&self, trans: &Transform, point: Vec3) -> bool {
+        match self {
+            Collider::Sphere { radius } => {
+                point.distance_squared(trans.translation) <= radius * radius
+            }
+            Collider::Cuboid { size } => {
+                let local = trans.rotation.inverse() * (point - trans.translation);
+                local.abs().cmple(*size).all()
+            }
+            Collider::Capsule {
+                half_height,
+                radius,
+            } => {
+                let local = trans.rotation.inverse() * (point - trans.translation);
+                let along = local.y.clamp(-half_height, *half_height);
+                local.distance_squared(Vec3::Y * along) <= radius * radius
+            }
+            Collider::Plane { normal } => {
+                (trans.rotation * *normal).dot(point - trans.translation) <= 0.0
+            }
+            Collider::ConvexHull(hull) => {
+                let local = trans.rotation.inverse() * (point - trans.translation);
+                hull.faces().iter().all(|f| f.normal.dot(local) <= f.offset)
+            }
+            // a soup of triangles has no inside
+            Collider::TriMesh(_) => false,
+            Collider::Compound(_) => self
+                .parts(trans)
+                .any(|(_, collider, part_trans)| collider.contains_point(&part_trans, point)),
+        }
+    }
+
+    // Thinnest the collider gets across any direction
+    pub(crate) fn min_width(&self) -> f32 {
+        match self {
+            Collider::Sphere { radius } => 2.0 * radius,
+            Collider::Cuboid { size } => 2.0 * size.min_element(),
+            Collider::Capsule { radius, .. } => 2.0 * radius,
+            Collider::ConvexHull(hull) => hull.min_width(),
+            // a plane can't be stepped over and a mesh is thin, neither can make a step longer
+            Collider::Plane { .. } | Collider::TriMesh(_) => 0.0,
+            Collider::Compound(compound) => compound
+                .parts()
+                .iter()
+                .map(|p| p.collider.min_width())
+                .reduce(f32::min)
+                .unwrap_or(0.0),
+        }
+    }
+
+    /// First time a collider moved along dir touches another, dir must be normalized
+    pub fn cast_shape(
+        &self,
+        trans: &Transform,
+        dir: Vec3,
+        max_toi: f32,
+        other: &Collider,
+        other_trans: &Transform,
+    ) -> Option<(f32, Vec3, Vec3)> {
+        let at = |toi: f32| Transform {
+            translation: trans.translation + dir * toi,
+            ..*trans
+        };
+        let hit = |toi: f32| {
+            self.contact(&at(toi), other, other_trans)
+                .and_then(|m| m.points.first().map(|p| (p.point_b, -m.normal)))
+        };
+
+        if let Some((point, normal)) = hit(0.0) {
+            return Some((0.0, point, normal));
+        }
+
+        // only the stretch where the bounds overlap can hold a hit
+        let start = self.aabb(trans);
+        let half = (start.max - start.min) * 0.5;
+        let bounds = other.aabb(other_trans);
+        let bounds = Aabb::new(bounds.min - half, bounds.max + half);
+        let (enter, exit) = bounds.ray_interval(trans.translation, dir, max_toi)?;
+
+        // steps shorter than both shapes together can't jump over the hit, two flat shapes
+        // would need endless steps
+        let width = self.min_width() + other.min_width();
+        if width <= 0.0 {
+            return None;
+        }
+        let step = 0.9 * width;
+        let mut clear = enter;
+        let mut blocked = None;
+        let mut t = enter;
+        loop {
+            if hit(t).is_some() {
+                blocked = Some(t);
+                break;
+            }
+            clear = t;
+            if t >= exit {
+                break;
+            }
+            t = (t + step).min(exit);
+        }
+        let mut blocked = blocked?;
+
+        for _ in 0..CAST_REFINE_STEPS {
+            let mid = (clear + blocked) * 0.5;
+            if hit(mid).is_some() {
+                blocked = mid;
+            } else {
+                clear = mid;
+            }
+        }
+        let (point, normal) = hit(blocked)?;
+        Some((clear, point, normal))
+    }
+}
+
+// First hit of a ray on a sphere, dir must be normalized
+fn ray_sphere(
+    origin: Vec3,
+    dir: Vec3,
+    center: Vec3,
+    radius: f32,
+    max_toi: f32,
+) -> Option<(f32, Vec3)> {
+    let d = origin - center;
+    let c = d.length_squared() - radius * radius;
+    if c <= 0.0 {
+        return Some((0.0, -dir));
+    }
+    let b = d.dot(dir);
+    let disc = b * b - c;
+    if b > 0.0 || disc < 0.0 {
+        return None;
+    }
+    let toi = -b - disc.sqrt();
+    (toi <= max_toi).then(|| (toi, (d + dir * toi).normalize_or_zero()))
+}
+
+// First hit of a ray on the points within radius of the segment a-b, dir must be normalized
+fn ray_capsule(
+    origin: Vec3,
+    dir: Vec3,
+    a: Vec3,
+    b: Vec3,
+    radius: f32,
+    max_toi: f32,
+) -> Option<(f32, Vec3)> {
+    let length = a.distance(b);
+    if length <= f32::EPSILON {
+        return ray_sphere(origin, dir, a, radius, max_toi);
+    }
+    let axis = (b - a) / length;
+    let m = origin - a;
+    let along = m.dot(axis).clamp(0.0, length);
+    if origin.distance_squared(a + axis * along) <= radius * radius {
+        return Some((0.0, -dir));
+    }
+
+    // the side is a cylinder around the axis, anything entering it past the ends hits a cap
+    let (dp, mp) = (dir - axis * dir.dot(axis), m - axis * m.dot(axis));
+    let (qa, qb, qc) = (dp.dot(dp), mp.dot(dp), mp.dot(mp) - radius * radius);
+    let disc = qb * qb - qa * qc;
+    if qa > f32::EPSILON && disc >= 0.0 {
+        let toi = (-qb - disc.sqrt()) / qa;
+        let along = (m + dir * toi).dot(axis);
+        if toi >= 0.0 && toi <= max_toi && (0.0..=length).contains(&along) {
+            return Some((toi, (mp + dp * toi).normalize_or_zero()));
+        }
+    }
+    [a, b]
+        .into_iter()
+        .filter_map(|end| ray_sphere(origin, dir, end, radius, max_toi))
+        .min_by(|x, y| x.0.partial_cmp(&y.0).unwrap_or(Ordering::Equal))
+}
+
+// Ray against one triangle from either side, the normal faces back along the ray
+fn ray_triangle(
+    origin: Vec3,
+    dir: Vec3,
+    [a, b, c]: [Vec3; 3],
+    max_toi: f32,
+) -> Option<(f32, Vec3)> {
+    let (e1, e2) = (b - a, c - a);
+    let p = dir.cross(e2);
+    let det = e1.dot(p);
+    if det.abs() <= f32::EPSILON {
+        return None;
+    }
+    let inv = 1.0 / det;
+    let s = origin - a;
+    let u = s.dot(p) * inv;
+    let q = s.cross(e1);
+    let v = dir.dot(q) * inv;
+    if u < 0.0 || v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let toi = e2.dot(q) * inv;
+    if toi < 0.0 || toi > max_toi {
+        return None;
+    }
+    let normal = e1.cross(e2).normalize_or_zero();
+    Some((
+        toi,
+        if normal.dot(dir) > 0.0 {
+            -normal
+        } else {
+            normal
+        },
+    ))
+}
+
+/// Ray casts, shape casts and overlap tests against every collider
+#[derive(SystemParam)]
+pub struct PhysicsQuery<'w, 's> {
+    broad_phase: Res<'w, BroadPhase>,
+    colliders: Query<
+        'w,
+        's,
+        (
+            &'static Collider,
+            &'static Transform,
+            Option<&'static CollisionGroups>,
+            Option<&'static Sensor>,
+        ),
+    >,
+}
+
+impl<'w, 's> PhysicsQuery<'w, 's> {
+    /// Closest collider along a ray, dir need not be normalized
+    pub fn cast_ray(
+        &self,
+        origin: Vec3,
+        dir: Vec3,
+        max_toi: f32,
+        filter: &QueryFilter,
+    ) -> Option<RayHit> {
+        self.ray_hits(origin, dir, max_toi, filter)
+            .min_by(|a, b| a.toi.partial_cmp(&b.toi).unwrap_or(Ordering::Equal))
+    }
+
+    /// Every collider along a ray, closest first
+    pub fn cast_ray_all(
+        &self,
+        origin: Vec3,
+        dir: Vec3,
+        max_toi: f32,
+        filter: &QueryFilter,
+    ) -> Vec<RayHit> {
+        let mut hits = self
+            .ray_hits(origin, dir, max_toi, filter)
+            .collect::<Vec<_>>();
+        hits.sort_by(|a, b| a.toi.partial_cmp(&b.toi).unwrap_or(Ordering::Equal));
+        hits
+    }
+
+    fn ray_hits<'a>(
+        &'a self,
+        origin: Vec3,
+        dir: Vec3,
+        max_toi: f32,
+        filter: &'a QueryFilter,
+    ) -> impl Iterator<Item = RayHit> + 'a {
+        let dir = dir.normalize_or_zero();
+        self.broad_phase
+            .strategy
+            .query_ray(origin, dir, max_toi)
+            .into_iter()
+            .filter_map(move |e| {
+                let (collider, trans) = self.candidate(e, filter)?;
+                let (toi, normal) = collider.cast_ray(trans, origin, dir, max_toi)?;
+                Some(RayHit {
+                    entity: e,
+                    toi,
+                    point: origin + dir * toi,
+                    normal,
+                })
+            })
+    }
+
+    /// First collider touched by a shape moved from trans along dir
+    pub fn cast_shape(
+        &self,
+        shape: &Collider,
+        trans: &Transform,
+        dir: Vec3,
+        max_toi: f32,
+        filter: &QueryFilter,
+    ) -> Option<ShapeHit> {
+        let dir = dir.normalize_or_zero();
+        let start = shape.aabb(trans);
+        let swept = start.union(&Aabb::new(
+            start.min + dir * max_toi,
+            start.max + dir * max_toi,
+        ));
+        self.broad_phase
+            .strategy
+            .query(&swept)
+            .into_iter()
+            .filter_map(|e| {
+                let (collider, other_trans) = self.candidate(e, filter)?;
+                let (toi, point, normal) =
+                    shape.cast_shape(trans, dir, max_toi, collider, other_trans)?;
+                Some(ShapeHit {
+                    entity: e,
+                    toi,
+                    point,
+                    normal,
+                })
+            })
+            .min_by(|a, b| a.toi.partial_cmp(&b.toi).unwrap_or(Ordering::Equal))
+    }
+
+    pub fn cast_sphere(
+        &self,
+        radius: f32,
+        origin: Vec3,
+        dir: Vec3,
+        max_toi: f32,
+        filter: &QueryFilter,
+    ) -> Option<ShapeHit> {
+        let trans = Transform::from_translation(origin);
+        self.cast_shape(&Collider::sphere(radius), &trans, dir, max_toi, filter)
+    }
+
+    /// Casts a box of the given half extents, oriented and placed by trans
+    pub fn cast_cuboid(
+        &self,
+        size: Vec3,
+        trans: &Transform,
+        dir: Vec3,
+        max_toi: f32,
+        filter: &QueryFilter,
+    ) -> Option<ShapeHit> {
+        let shape = Collider::Cuboid { size };
+        self.cast_shape(&shape, trans, dir, max_toi, filter)
+    }
+
+    /// Colliders containing a world space point
+    pub fn intersect_point(&self, point: Vec3, filter: &QueryFilter) -> Vec<Entity> {
+        self.broad_phase
+            .strategy
+            .query(&Aabb::new(point, point))
+            .into_iter()
+            .filter(|e| {
+                self.candidate(*e, filter)
+                    .map_or(false, |(collider, trans)| {
+                        collider.contains_point(trans, point)
+                    })
+            })
+            .collect()
+    }
+
+    /// Colliders overlapping a world space box
+    pub fn intersect_aabb(&self, aabb: &Aabb, filter: &QueryFilter) -> Vec<Entity> {
+        let shape = Collider::Cuboid {
+            size: (aabb.max - aabb.min) * 0.5,
+        };
+        let trans = Transform::from_translation(aabb.center());
+        self.broad_phase
+            .strategy
+            .query(aabb)
+            .into_iter()
+            .filter(|e| {
+                self.candidate(*e, filter)
+                    .map_or(false, |(collider, other_trans)| {
+                        shape.contact(&trans, collider, other_trans).is_some()
+                    })
+            })
+            .collect()
+    }
+
+    // The collider of an entity the filter lets through
+    fn candidate(&self, entity: Entity, filter: &QueryFilter) -> Option<(&Collider, &Transform)> {
+        if !filter.test(entity) {
+            return None;
+        }
+        let (collider, trans, groups, sensor) = self.colliders.get(entity).ok()?;
+        if filter.exclude_sensors && sensor.is_some() {
+            return None;
+        }
+        if let Some(filter_groups) = filter.groups {
+            if !filter_groups.interacts_with(&groups.copied().unwrap_or_default()) {
+                return None;
+            }
+        }
+        Some((collider, trans))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::SystemState;
+
+    use super::*;
+    use crate::physics::{
+        tests::{app, step},
+        Static,
+    };
+
+    const EPS: f32 = 1e-3;
+
+    struct Scene {
+        app: App,
+        sphere: Entity,
+        cuboid: Entity,
+        plane: Entity,
+        wall: Entity,
+    }
+
+    // A sphere ahead along -z with a wall behind it, a box along +x and the ground below
+    fn scene() -> Scene {
+        let mut app = app();
+        let mut spawn = |collider: Collider, trans: Transform| {
+            app.world
+                .spawn()
+                .insert(trans)
+                .insert(collider)
+                .insert(Static)
+                .id()
+        };
+        let sphere = spawn(Collider::sphere(1.0), Transform::from_xyz(0.0, 0.0, -5.0));
+        let cuboid = spawn(
+            Collider::cuboid(1.0, 1.0, 1.0),
+            Transform::from_xyz(5.0, 0.0, 0.0),
+        );
+        let plane = spawn(Collider::plane(Vec3::Y), Transform::from_xyz(0.0, -2.0, 0.0));
+        let wall = spawn(
+            Collider::cuboid(4.0, 4.0, 1.0),
+            Transform::from_xyz(0.0, 0.0, -10.0),
+        );
+        // fills the broad phase
+        step(&mut app);
+        Scene {
+            app,
+            sphere,
+            cuboid,
+            plane,
+            wall,
+        }
+    }
+
+    fn assert_hit(hit: Option<RayHit>, entity: Entity, toi: f32, normal: Vec3) {
+        let hit = hit.expect("ray missed");
+        assert_eq!(hit.entity, entity);
+        assert!((hit.toi - toi).abs() < EPS, "toi {} != {}", hit.toi, toi);
+        assert!(hit.normal.abs_diff_eq(normal, EPS), "normal {}", hit.normal);
+    }
+
+    #[test]
+    fn cast_ray_hits_each_shape() {
+        let mut scene = scene();
+        let mut state: SystemState<PhysicsQuery> = SystemState::new(&mut scene.app.world);
+        let query = state.get_mut(&mut scene.app.world);
+        let filter = QueryFilter::default();
+
+        let hit = query.cast_ray(Vec3::ZERO, -Vec3::Z, 100.0, &filter);
+        assert_hit(hit, scene.sphere, 4.0, Vec3::Z);
+        assert!(hit.unwrap().point.abs_diff_eq(Vec3::new(0.0, 0.0, -4.0), EPS));
+
+        // dir need not be normalized
+        let hit = query.cast_ray(Vec3::ZERO, Vec3::X * 3.0, 100.0, &filter);
+        assert_hit(hit, scene.cuboid, 4.0, -Vec3::X);
+
+        let hit = query.cast_ray(Vec3::ZERO, -Vec3::Y, 100.0, &filter);
+        assert_hit(hit, scene.plane, 2.0, Vec3::Y);
+
+        let hit = query.cast_ray(Vec3::new(0.0, 5.0, 0.0), Vec3::Y, 100.0, &filter);
+        assert!(hit.is_none());
+    }
+
+    #[test]
+    fn cast_ray_stops_at_max_toi() {
+        let mut scene = scene();
+        let mut state: SystemState<PhysicsQuery> = SystemState::new(&mut scene.app.world);
+        let query = state.get_mut(&mut scene.app.world);
+        let filter = QueryFilter::default();
+
+        assert!(query.cast_ray(Vec3::ZERO, -Vec3::Z, 3.9, &filter).is_none());
+        let hit = query.cast_ray(Vec3::ZERO, -Vec3::Z, 4.1, &filter);
+        assert_hit(hit, scene.sphere, 4.0, Vec3::Z);
+
+        let hits = query.cast_ray_all(Vec3::ZERO, -Vec3::Z, 8.0, &filter);
+        assert_eq!(hits.len(), 1);
+        let hits = query.cast_ray_all(Vec3::ZERO, -Vec3::Z, 100.0, &filter);
+        assert_eq!(
+            hits.iter().map(|h| h.entity).collect::<Vec<_>>(),
+            vec![scene.sphere, scene.wall]
+        );
+        assert_hit(Some(hits[1]), scene.wall, 9.0, Vec3::Z);
+    }
+
+    #[test]
+    fn filter_skips_excluded_colliders() {
+        let mut scene = scene();
+        scene
+            .app
+            .world
+            .entity_mut(scene.wall)
+            .insert(CollisionGroups::new(0b10, CollisionGroups::ALL));
+        let mut state: SystemState<PhysicsQuery> = SystemState::new(&mut scene.app.world);
+        let query = state.get_mut(&mut scene.app.world);
+
+        // the ray goes through the excluded sphere to the wall
+        let filter = QueryFilter::default().exclude(scene.sphere);
+        let hit = query.cast_ray(Vec3::ZERO, -Vec3::Z, 100.0, &filter);
+        assert_hit(hit, scene.wall, 9.0, Vec3::Z);
+
+        let not_sphere = |e: Entity| e != scene.sphere;
+        let filter = QueryFilter::default().predicate(&not_sphere);
+        let hits = query.cast_ray_all(Vec3::ZERO, -Vec3::Z, 100.0, &filter);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].entity, scene.wall);
+
+        // and the wall is in a group the query doesn't collide with
+        let filter = QueryFilter::default()
+            .exclude(scene.sphere)
+            .groups(CollisionGroups::new(CollisionGroups::ALL, 0b01));
+        assert!(query.cast_ray(Vec3::ZERO, -Vec3::Z, 100.0, &filter).is_none());
+
+        scene.app.world.entity_mut(scene.sphere).insert(Sensor);
+        let query = state.get_mut(&mut scene.app.world);
+        let filter = QueryFilter::default().exclude_sensors();
+        let hit = query.cast_ray(Vec3::ZERO, -Vec3::Z, 100.0, &filter);
+        assert_hit(hit, scene.wall, 9.0, Vec3::Z);
+    }
+
+    #[test]
+    fn cast_shape_hits_each_shape() {
+        let mut scene = scene();
+        let mut state: SystemState<PhysicsQuery> = SystemState::new(&mut scene.app.world);
+        let query = state.get_mut(&mut scene.app.world);
+        let filter = QueryFilter::default();
+
+        let hit = query
+            .cast_sphere(0.5, Vec3::ZERO, Vec3::X, 100.0, &filter)
+            .unwrap();
+        assert_eq!(hit.entity, scene.cuboid);
+        assert!((hit.toi - 3.5).abs() < EPS, "toi {}", hit.toi);
+        assert!(hit.normal.abs_diff_eq(-Vec3::X, EPS));
+        assert!((hit.point.x - 4.0).abs() < EPS);
+
+        let hit = query
+            .cast_sphere(0.5, Vec3::ZERO, -Vec3::Z, 100.0, &filter)
+            .unwrap();
+        assert_eq!(hit.entity, scene.sphere);
+        assert!((hit.toi - 3.5).abs() < EPS, "toi {}", hit.toi);
+        assert!(hit.normal.abs_diff_eq(Vec3::Z, EPS));
+        assert!((hit.point.z + 4.0).abs() < EPS);
+
+        let hit = query
+            .cast_cuboid(
+                Vec3::splat(0.5),
+                &Transform::default(),
+                -Vec3::Y,
+                100.0,
+                &filter,
+            )
+            .unwrap();
+        assert_eq!(hit.entity, scene.plane);
+        assert!((hit.toi - 1.5).abs() < EPS, "toi {}", hit.toi);
+        assert!(hit.normal.abs_diff_eq(Vec3::Y, EPS));
+
+        assert!(query
+            .cast_sphere(0.5, Vec3::ZERO, Vec3::X, 3.4, &filter)
+            .is_none());
+        let filter = QueryFilter::default().exclude(scene.cuboid);
+        assert!(query
+            .cast_sphere(0.5, Vec3::ZERO, Vec3::X, 100.0, &filter)
+            .is_none());
+    }
+
+    #[test]
+    fn intersections() {
+        let mut scene = scene();
+        let mut state: SystemState<PhysicsQuery> = SystemState::new(&mut scene.app.world);
+        let query = state.get_mut(&mut scene.app.world);
+        let filter = QueryFilter::default();
+
+        let inside = query.intersect_point(Vec3::new(0.3, 0.3, -5.3), &filter);
+        assert_eq!(inside, vec![scene.sphere]);
+        let inside = query.intersect_point(Vec3::new(5.9, 0.9, 0.9), &filter);
+        assert_eq!(inside, vec![scene.cuboid]);
+        let inside = query.intersect_point(Vec3::new(20.0, -3.0, 0.0), &filter);
+        assert_eq!(inside, vec![scene.plane]);
+        assert!(query.intersect_point(Vec3::ZERO, &filter).is_empty());
+        let filter = QueryFilter::default().exclude(scene.cuboid);
+        assert!(query
+            .intersect_point(Vec3::new(5.9, 0.9, 0.9), &filter)
+            .is_empty());
+
+        // just reaching into the back of the sphere, short of the wall
+        let filter = QueryFilter::default();
+        let aabb = Aabb::from_center(Vec3::new(0.0, 0.0, -6.5), Vec3::splat(0.6));
+        assert_eq!(query.intersect_aabb(&aabb, &filter), vec![scene.sphere]);
+        let aabb = Aabb::from_center(Vec3::new(0.0, 0.0, -7.5), Vec3::splat(0.6));
+        assert!(query.intersect_aabb(&aabb, &filter).is_empty());
+        let aabb = Aabb::from_center(Vec3::new(3.0, -2.0, 0.0), Vec3::splat(1.5));
+        let mut overlapping = query.intersect_aabb(&aabb, &filter);
+        overlapping.sort();
+        let mut expected = vec![scene.cuboid, scene.plane];
+        expected.sort();
+        assert_eq!(overlapping, expected);
+    }
+}
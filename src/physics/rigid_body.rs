@@ -1,8 +1,12 @@
-use bevy::prelude::*;
+use std::ops::{BitOr, BitOrAssign};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
 use bevy_inspector_egui::Inspectable;
 
+use super::InverseInertiaTensor;
+
 
-#[derive(Component, Inspectable, Debug)]
+#[derive(Component, Inspectable, Debug, Clone, Copy)]
 pub struct RigidBody;
 
 impl RigidBody {
@@ -24,16 +28,33 @@ impl RigidBody {
         center_of_mass_world.0 + trans.rotation * local_point
     }
 
+    /// Changes the velocities as an impulse at a world space point would, the body has to be
+    /// awake or it is woken by the change next step
+    pub fn apply_impulse_at_point(
+        impulse: Vec3,
+        world_point: Vec3,
+        linear_velocity: &mut LinearVelocity,
+        angular_velocity: &mut AngularVelocity,
+        inv_mass: &InvMass,
+        inverse_inertia_tensor: &InverseInertiaTensor,
+        center_of_mass_world: &CenterOfMassWorld,
+    ) {
+        let arm = world_point - center_of_mass_world.0;
+        linear_velocity.0 += impulse * inv_mass.0;
+        angular_velocity.0 += inverse_inertia_tensor.0 * arm.cross(impulse);
+    }
+
     pub fn update(
         transform: &mut Transform,
         linear_velocity: &mut LinearVelocity,
         angular_velocity: &mut AngularVelocity,
         center_of_mass_world: &CenterOfMassWorld,
-        inertia_tensor: &InertiaTensor,        
+        inertia_tensor: &InertiaTensor,
+        inverse_inertia_tensor: &InverseInertiaTensor,
         dt: f32,
     ) {
-        // apply linear velocity
-        transform.translation += linear_velocity.0 * dt;
+        // apply linear velocity, the centre of mass carries the body along
+        let center_of_mass = center_of_mass_world.0 + linear_velocity.0 * dt;
 
         // we have an angular velocity around the centre of mass, this needs to be converted to
         // relative body translation. This way we can properly update the rotation of the model
@@ -41,13 +62,15 @@ impl RigidBody {
         let com_to_position = transform.translation - center_of_mass_world.0;
 
         // total torque is equal to external applied torques + internal torque (precession)
-        // T = T_external + omega x I * omega
+        // T_external = I * a + w x I * w
         // T_external = 0 because it was applied in the collision response function
-        // T = Ia = w x I * w
-        // a = I^-1 (w x I * w)
+        // I * a = -(w x I * w)
+        // a = -I^-1 (w x I * w)
+        // the world inverse is already zero for bodies that impulses don't turn, inverting
+        // a zero tensor here would fill the velocity with NaN
         let orientation = Mat3::from_quat(transform.rotation);
         let inertia_tensor = orientation * inertia_tensor.0 * orientation.transpose();
-        let alpha = inertia_tensor.inverse()
+        let alpha = -inverse_inertia_tensor.0
             * (angular_velocity.0
                 .cross(inertia_tensor * angular_velocity.0));
         angular_velocity.0 += alpha * dt;
@@ -64,7 +87,7 @@ impl RigidBody {
         transform.rotation = (dq * transform.rotation).normalize();
 
         // now get the new body position
-        transform.translation = center_of_mass_world.0 + dq * com_to_position;
+        transform.translation = center_of_mass + dq * com_to_position;
 
         // // update center of mass and inverse inertia tensor
         // center_of_mass_world =
@@ -75,32 +98,200 @@ impl RigidBody {
     }
 }
 
-#[derive(Component, Inspectable, Debug)]
+/// Pushes bodies by entity from gameplay systems. The velocities change straight away and a
+/// sleeping body wakes up next step
+#[derive(SystemParam)]
+pub struct Impulses<'w, 's> {
+    bodies: Query<
+        'w,
+        's,
+        (
+            &'static mut LinearVelocity,
+            &'static mut AngularVelocity,
+            &'static InvMass,
+            &'static InverseInertiaTensor,
+            &'static CenterOfMassWorld,
+        ),
+    >,
+}
+
+impl<'w, 's> Impulses<'w, 's> {
+    /// Impulse through the center of mass, false if the entity isn't a body
+    pub fn apply_impulse(&mut self, entity: Entity, impulse: Vec3) -> bool {
+        match self.bodies.get_mut(entity) {
+            Ok((mut linear_velocity, _, inv_mass, ..)) => {
+                linear_velocity.0 += impulse * inv_mass.0;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Impulse at a world space point, which turns the body as well. False if the entity
+    /// isn't a body
+    pub fn apply_impulse_at_point(&mut self, entity: Entity, impulse: Vec3, point: Vec3) -> bool {
+        match self.bodies.get_mut(entity) {
+            Ok((mut linear_velocity, mut angular_velocity, inv_mass, inv_inertia, com_world)) => {
+                RigidBody::apply_impulse_at_point(
+                    impulse,
+                    point,
+                    &mut linear_velocity,
+                    &mut angular_velocity,
+                    inv_mass,
+                    inv_inertia,
+                    com_world,
+                );
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+#[derive(Component, Inspectable, Debug, Clone, Copy)]
 pub struct Static;
 
-#[derive(Component, Inspectable, Debug, Default)]
+/// Moved by the game rather than by forces or contacts. It has infinite mass to the solver
+/// and pushes dynamic bodies with the velocity it moves at
+#[derive(Component, Inspectable, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kinematic {
+    /// Transform is set by the game, velocity is worked out from how far it moved
+    PositionBased,
+    /// Velocities are set by the game and integrated, gravity is ignored
+    VelocityBased,
+}
+
+#[derive(Component, Inspectable, Debug, Default, Clone, Copy)]
 pub struct LinearVelocity(pub Vec3);
 
-#[derive(Component, Inspectable, Debug, Default)]
+#[derive(Component, Inspectable, Debug, Default, Clone, Copy)]
 pub struct AngularVelocity(pub Vec3);
 
-#[derive(Component, Inspectable, Debug, Default)]
+/// Axes a body can't move along or turn around, combine them with |
+#[derive(Component, Inspectable, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LockedAxes(u8);
+
+impl LockedAxes {
+    pub const TRANSLATION_LOCKED_X: Self = Self(1 << 0);
+    pub const TRANSLATION_LOCKED_Y: Self = Self(1 << 1);
+    pub const TRANSLATION_LOCKED_Z: Self = Self(1 << 2);
+    pub const TRANSLATION_LOCKED: Self = Self(0b111);
+    pub const ROTATION_LOCKED_X: Self = Self(1 << 3);
+    pub const ROTATION_LOCKED_Y: Self = Self(1 << 4);
+    pub const ROTATION_LOCKED_Z: Self = Self(1 << 5);
+    pub const ROTATION_LOCKED: Self = Self(0b111 << 3);
+    /// Kept on the XY plane, only turning around Z
+    pub const PLANAR: Self = Self(
+        Self::TRANSLATION_LOCKED_Z.0 | Self::ROTATION_LOCKED_X.0 | Self::ROTATION_LOCKED_Y.0,
+    );
+
+    pub fn empty() -> Self {
+        Self(0)
+    }
+
+    pub fn contains(&self, other: LockedAxes) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// 1 on the axes free to move along, 0 on locked ones
+    pub fn linear_factor(&self) -> Vec3 {
+        self.factor(0)
+    }
+
+    /// 1 on the axes free to turn around, 0 on locked ones
+    pub fn angular_factor(&self) -> Vec3 {
+        self.factor(3)
+    }
+
+    fn factor(&self, shift: u8) -> Vec3 {
+        let free = |axis: u8| if self.0 & (1 << (shift + axis)) == 0 { 1.0 } else { 0.0 };
+        Vec3::new(free(0), free(1), free(2))
+    }
+}
+
+impl BitOr for LockedAxes {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for LockedAxes {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// Sweeps the body along its motion every step so it can't pass through thin colliders
+/// when moving fast
+#[derive(Component, Inspectable, Debug, Default, Clone, Copy)]
+pub struct Ccd;
+
+/// World space force applied every step until it is changed, in newtons at the center of mass
+#[derive(Component, Inspectable, Debug, Default, Clone, Copy)]
+pub struct ExternalForce(pub Vec3);
+
+impl ExternalForce {
+    /// The force and the torque it makes when pushing at a world space point
+    pub fn at_point(
+        force: Vec3,
+        world_point: Vec3,
+        center_of_mass_world: &CenterOfMassWorld,
+    ) -> (Self, ExternalTorque) {
+        let arm = world_point - center_of_mass_world.0;
+        (Self(force), ExternalTorque(arm.cross(force)))
+    }
+}
+
+/// World space torque applied every step until it is changed
+#[derive(Component, Inspectable, Debug, Default, Clone, Copy)]
+pub struct ExternalTorque(pub Vec3);
+
+/// Slows a body down, the fraction of velocity lost is about the damping times the step
+#[derive(Component, Inspectable, Debug, Default, Clone, Copy)]
+pub struct Damping {
+    pub linear: f32,
+    pub angular: f32,
+}
+
+/// Multiplies the gravity on one body, 0 to float
+#[derive(Component, Inspectable, Debug, Clone, Copy)]
+pub struct GravityScale(pub f32);
+
+impl Default for GravityScale {
+    fn default() -> Self {
+        GravityScale(1.0)
+    }
+}
+
+#[derive(Component, Inspectable, Debug, Default, Clone, Copy)]
 pub struct Elasticity(pub f32); // assumed [0,1]
 
-#[derive(Component, Inspectable, Debug, Default)]
+#[derive(Component, Inspectable, Debug, Default, Clone, Copy)]
 pub struct Friction(pub f32); // assumed [0,1]
 
-#[derive(Component, Inspectable, Debug, Default)]
+#[derive(Component, Inspectable, Debug, Default, Clone, Copy)]
 pub struct Mass(pub f32);
 
-#[derive(Component, Inspectable, Debug, Default)]
+#[derive(Component, Inspectable, Debug, Default, Clone, Copy)]
 pub struct InvMass(pub f32);
 
-#[derive(Component, Inspectable, Debug, Default)]
+/// Mass per unit volume, used to work out the mass when no Mass is given
+#[derive(Component, Inspectable, Debug, Clone, Copy)]
+pub struct Density(pub f32);
+
+impl Default for Density {
+    fn default() -> Self {
+        Density(1.0)
+    }
+}
+
+#[derive(Component, Inspectable, Debug, Default, Clone, Copy)]
 pub struct CenterOfMass(pub Vec3);
 
-#[derive(Component, Inspectable, Debug, Default)]
+#[derive(Component, Inspectable, Debug, Default, Clone, Copy)]
 pub struct CenterOfMassWorld(pub Vec3);
 
-#[derive(Component, Inspectable, Debug, Default)]
+#[derive(Component, Inspectable, Debug, Default, Clone, Copy)]
 pub struct InertiaTensor(pub Mat3);
\ No newline at end of file
@@ -0,0 +1,112 @@
+use std::cmp::Ordering;
+
+use bevy::{prelude::*, utils::HashMap};
+
+use super::Aabb;
+
+/// Sort and sweep over entity bounds. Bounds are kept sorted along the axis they are most
+/// spread out on, frame to frame the order barely changes so the sort stays cheap
+#[derive(Debug, Clone, Default)]
+pub struct SweepAndPrune {
+    entries: Vec<(Entity, Aabb)>,
+    index: HashMap<Entity, usize>,
+    axis: usize,
+    // entries are in order along the axis
+    sorted: bool,
+}
+
+impl SweepAndPrune {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Adds an entity, or moves it if already present
+    pub fn insert(&mut self, entity: Entity, aabb: Aabb) {
+        match self.index.get(&entity) {
+            Some(i) => self.entries[*i].1 = aabb,
+            None => {
+                self.index.insert(entity, self.entries.len());
+                self.entries.push((entity, aabb));
+            }
+        }
+        self.sorted = false;
+    }
+
+    pub fn remove(&mut self, entity: Entity) {
+        if let Some(i) = self.index.remove(&entity) {
+            self.entries.swap_remove(i);
+            if let Some((moved, _)) = self.entries.get(i) {
+                self.index.insert(*moved, i);
+            }
+            self.sorted = false;
+        }
+    }
+
+    /// Every pair of entities with overlapping bounds, lowest entity first
+    pub fn pairs(&mut self) -> Vec<(Entity, Entity)> {
+        self.sort();
+
+        let axis = self.axis;
+        let mut pairs = Vec::new();
+        for (i, (ea, a)) in self.entries.iter().enumerate() {
+            for (eb, b) in self.entries[i + 1..].iter() {
+                // sorted by min, nothing further along can reach back to a
+                if b.min[axis] > a.max[axis] {
+                    break;
+                }
+                if a.intersects(b) {
+                    pairs.push(if ea < eb { (*ea, *eb) } else { (*eb, *ea) });
+                }
+            }
+        }
+        pairs
+    }
+
+    /// Entities whose bounds overlap the aabb
+    pub fn query(&self, aabb: &Aabb) -> Vec<Entity> {
+        let axis = self.axis;
+        self.entries
+            .iter()
+            .take_while(|(_, b)| !self.sorted || b.min[axis] <= aabb.max[axis])
+            .filter(|(_, b)| b.intersects(aabb))
+            .map(|(e, _)| *e)
+            .collect()
+    }
+
+    // Picks the axis with the most spread and sorts on it
+    fn sort(&mut self) {
+        if self.entries.is_empty() {
+            return;
+        }
+
+        let n = self.entries.len() as f32;
+        let (sum, sum_sq) = self.entries.iter().fold((Vec3::ZERO, Vec3::ZERO), |(s, sq), (_, b)| {
+            let c = b.center();
+            (s + c, sq + c * c)
+        });
+        let variance = sum_sq / n - (sum / n) * (sum / n);
+        self.axis = if variance.x >= variance.y && variance.x >= variance.z {
+            0
+        } else if variance.y >= variance.z {
+            1
+        } else {
+            2
+        };
+
+        // stable sort runs in close to linear time on nearly sorted input
+        let axis = self.axis;
+        self.entries.sort_by(|(_, a), (_, b)| {
+            a.min[axis]
+                .partial_cmp(&b.min[axis])
+                .unwrap_or(Ordering::Equal)
+        });
+        for (i, (e, _)) in self.entries.iter().enumerate() {
+            self.index.insert(*e, i);
+        }
+        self.sorted = true;
+    }
+}
@@ -0,0 +1,167 @@
+use bevy::{prelude::*, utils::HashMap};
+
+use super::{
+    AngularVelocity, Contacts, ExternalForce, ExternalTorque, InertiaTensor, Joint, Kinematic,
+    LinearVelocity, Mass, PhysicsTime, Static,
+};
+
+/// On a body that has settled, it is skipped by the simulation until something disturbs it
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Sleeping;
+
+/// How long a body has been still, and where it was when it fell asleep
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct SleepState {
+    /// Seconds spent below the energy threshold
+    pub still_for: f32,
+    // anything moving the body away from here while asleep wakes it
+    pose: Option<Transform>,
+}
+
+pub struct SleepSettings {
+    pub enabled: bool,
+    /// Kinetic energy per unit of mass a body counts as still below
+    pub energy_threshold: f32,
+    /// Seconds every body of an island has to stay still before it sleeps
+    pub time_to_sleep: f32,
+}
+
+impl Default for SleepSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            energy_threshold: 0.01,
+            time_to_sleep: 0.5,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Island {
+    sleeping: bool,
+    awake: bool,
+    // an awake body is still moving
+    restless: bool,
+    disturbed: bool,
+}
+
+// Groups bodies touching or jointed together into islands. An island sleeps once all of its
+// bodies have been still long enough, and wakes when anything in it is disturbed
+pub fn island_system(
+    mut commands: Commands,
+    settings: Res<SleepSettings>,
+    pt: Res<PhysicsTime>,
+    contacts: Res<Contacts>,
+    joints: Query<(&Joint, ChangeTrackers<Joint>)>,
+    kinematic: Query<(&LinearVelocity, &AngularVelocity), With<Kinematic>>,
+    forces: Query<(Option<&ExternalForce>, Option<&ExternalTorque>)>,
+    mut bodies: Query<
+        (
+            Entity,
+            &Transform,
+            (&mut LinearVelocity, &mut AngularVelocity),
+            (&Mass, &InertiaTensor),
+            &mut SleepState,
+            Option<&Sleeping>,
+        ),
+        (Without<Static>, Without<Kinematic>),
+    >,
+) {
+    let entities = bodies.iter().map(|(e, ..)| e).collect::<Vec<_>>();
+    let index = entities
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (*e, i))
+        .collect::<HashMap<_, _>>();
+    let mut parent = (0..entities.len()).collect::<Vec<_>>();
+    let mut disturbed = vec![false; entities.len()];
+
+    // static and kinematic bodies don't join islands, a moving kinematic body wakes what it touches.
+    // Sensors hold nothing up so they neither join nor wake
+    for contact in contacts.0.iter().filter(|c| !c.sensor) {
+        match (index.get(&contact.entity_a), index.get(&contact.entity_b)) {
+            (Some(a), Some(b)) => union(&mut parent, *a, *b),
+            (Some(i), None) | (None, Some(i)) => {
+                let other = if index.contains_key(&contact.entity_a) {
+                    contact.entity_b
+                } else {
+                    contact.entity_a
+                };
+                if let Ok((lin_vel, ang_vel)) = kinematic.get(other) {
+                    disturbed[*i] |= lin_vel.0 != Vec3::ZERO || ang_vel.0 != Vec3::ZERO;
+                }
+            }
+            _ => {}
+        }
+    }
+    // a new or changed joint wakes both ends
+    for (joint, tracker) in joints.iter() {
+        let (a, b) = (index.get(&joint.body_a), index.get(&joint.body_b));
+        if let (Some(a), Some(b)) = (a, b) {
+            union(&mut parent, *a, *b);
+        }
+        if tracker.is_changed() {
+            for i in a.into_iter().chain(b) {
+                disturbed[*i] = true;
+            }
+        }
+    }
+
+    let mut islands = HashMap::<usize, Island>::default();
+    for (e, trans, (lin_vel, ang_vel), (mass, inertia), mut state, sleeping) in bodies.iter_mut() {
+        let i = index[&e];
+        let island = islands.entry(find(&mut parent, i)).or_default();
+        // a body under a force is never still, even when something holds it in place
+        let pushed = forces.get(e).map_or(false, |(force, torque)| {
+            force.map_or(false, |f| f.0 != Vec3::ZERO) || torque.map_or(false, |t| t.0 != Vec3::ZERO)
+        });
+        if sleeping.is_some() {
+            // pushed or moved by hand since falling asleep
+            let pose = *state.pose.get_or_insert(*trans);
+            disturbed[i] |= pushed
+                || pose != *trans
+                || lin_vel.0 != Vec3::ZERO
+                || ang_vel.0 != Vec3::ZERO;
+            island.sleeping = true;
+        } else {
+            let local_ang_vel = trans.rotation.inverse() * ang_vel.0;
+            let rotational = local_ang_vel.dot(inertia.0 * local_ang_vel) / mass.0.max(f32::EPSILON);
+            let energy = 0.5 * (lin_vel.0.length_squared() + rotational);
+            state.still_for = if energy < settings.energy_threshold && !pushed {
+                state.still_for + pt.time
+            } else {
+                0.0
+            };
+            island.awake = true;
+            island.restless |= state.still_for < settings.time_to_sleep;
+        }
+        island.disturbed |= disturbed[i];
+    }
+
+    for (e, _, (mut lin_vel, mut ang_vel), _, mut state, sleeping) in bodies.iter_mut() {
+        let island = &islands[&find(&mut parent, index[&e])];
+        let wake = !settings.enabled || island.disturbed || (island.sleeping && island.restless);
+        if sleeping.is_some() && wake {
+            commands.entity(e).remove::<Sleeping>();
+            state.still_for = 0.0;
+            state.pose = None;
+        } else if sleeping.is_none() && !wake && island.awake && !island.restless {
+            commands.entity(e).insert(Sleeping);
+            lin_vel.0 = Vec3::ZERO;
+            ang_vel.0 = Vec3::ZERO;
+        }
+    }
+}
+
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+fn union(parent: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parent, a), find(parent, b));
+    parent[a] = b;
+}
@@ -0,0 +1,208 @@
+use bevy::{ecs::world::EntityMut, prelude::*, utils::HashSet};
+
+use super::{
+    AngularVelocity, BroadPhase, Ccd, CenterOfMass, CenterOfMassWorld, Collider, CollidingPairs,
+    CollisionEvents, CollisionGroups, Contacts, Damping, Density, Elasticity, ExternalForce,
+    ExternalTorque, Friction, GravityScale, InertiaTensor, InvMass, InverseInertiaTensor, Joint,
+    Kinematic, KinematicTarget, LinearVelocity, LockedAxes, Mass, PhysicsTime, PhysicsTimestep,
+    RigidBody, Sensor, SleepState, Sleeping, Static, TransformInterpolation,
+};
+
+/// Everything the simulation carries from one step to the next. Restoring a snapshot and
+/// stepping again gives the same results bit for bit, for replays and rollback.
+///
+/// Settings like Gravity and SolverSettings are left out. Bodies, colliders and joints that
+/// weren't captured are left alone on restore and handed back to the caller. The physics has
+/// no randomness of its own
+#[derive(Clone)]
+pub struct PhysicsSnapshot {
+    bodies: Vec<BodySnapshot>,
+    timestep: PhysicsTimestep,
+    time: PhysicsTime,
+    broad_phase: BroadPhase,
+    contacts: Contacts,
+    colliding: CollidingPairs,
+}
+
+// Every physics component on one entity, None where the entity doesn't have it
+#[derive(Clone)]
+struct BodySnapshot {
+    entity: Entity,
+    transform: Option<Transform>,
+    interpolation: Option<TransformInterpolation>,
+    collider: Option<Collider>,
+    kind: (Option<RigidBody>, Option<Static>, Option<Kinematic>, Option<KinematicTarget>),
+    velocity: (Option<LinearVelocity>, Option<AngularVelocity>),
+    mass: (
+        Option<Mass>,
+        Option<InvMass>,
+        Option<Density>,
+        Option<CenterOfMass>,
+        Option<CenterOfMassWorld>,
+        Option<InertiaTensor>,
+        Option<InverseInertiaTensor>,
+    ),
+    material: (Option<Elasticity>, Option<Friction>),
+    forces: (
+        Option<ExternalForce>,
+        Option<ExternalTorque>,
+        Option<Damping>,
+        Option<GravityScale>,
+        Option<LockedAxes>,
+    ),
+    sleep: (Option<SleepState>, Option<Sleeping>),
+    collision: (Option<CollisionGroups>, Option<Sensor>, Option<Ccd>, Option<CollisionEvents>),
+    joint: Option<Joint>,
+}
+
+impl PhysicsSnapshot {
+    /// Copies the state of every body and joint along with the physics resources
+    pub fn capture(world: &mut World) -> Self {
+        let entities = world
+            .query_filtered::<Entity, Or<(With<Collider>, With<RigidBody>, With<Joint>)>>()
+            .iter(world)
+            .collect::<Vec<_>>();
+        let bodies = entities
+            .into_iter()
+            .map(|entity| {
+                let e = world.entity(entity);
+                BodySnapshot {
+                    entity,
+                    transform: e.get().copied(),
+                    interpolation: e.get().copied(),
+                    collider: e.get().cloned(),
+                    kind: (e.get().copied(), e.get().copied(), e.get().copied(), e.get().copied()),
+                    velocity: (e.get().copied(), e.get().copied()),
+                    mass: (
+                        e.get().copied(),
+                        e.get().copied(),
+                        e.get().copied(),
+                        e.get().copied(),
+                        e.get().copied(),
+                        e.get().copied(),
+                        e.get().copied(),
+                    ),
+                    material: (e.get().copied(), e.get().copied()),
+                    forces: (
+                        e.get().copied(),
+                        e.get().copied(),
+                        e.get().copied(),
+                        e.get().copied(),
+                        e.get().copied(),
+                    ),
+                    sleep: (e.get().copied(), e.get().copied()),
+                    collision: (
+                        e.get().copied(),
+                        e.get().copied(),
+                        e.get().copied(),
+                        e.get().copied(),
+                    ),
+                    joint: e.get().cloned(),
+                }
+            })
+            .collect();
+
+        Self {
+            bodies,
+            timestep: world.resource::<PhysicsTimestep>().clone(),
+            time: *world.resource::<PhysicsTime>(),
+            broad_phase: world.resource::<BroadPhase>().clone(),
+            contacts: world.resource::<Contacts>().clone(),
+            colliding: world.resource::<CollidingPairs>().clone(),
+        }
+    }
+
+    /// Puts every captured body and resource back as it was. Components are written in
+    /// place, and added or removed where that changed since the capture.
+    ///
+    /// Returns the bodies, colliders and joints the snapshot doesn't have, which are left as
+    /// they are. They may belong to gameplay, despawn them if the replay has to be exact
+    pub fn restore(&self, world: &mut World) -> Vec<Entity> {
+        let captured = self.bodies.iter().map(|b| b.entity).collect::<HashSet<_>>();
+        let uncaptured = world
+            .query_filtered::<Entity, Or<(With<Collider>, With<RigidBody>, With<Joint>)>>()
+            .iter(world)
+            .filter(|e| !captured.contains(e))
+            .collect::<Vec<_>>();
+
+        for body in self.bodies.iter() {
+            let mut e = match world.get_entity_mut(body.entity) {
+                Some(e) => e,
+                None => {
+                    warn!("{:?} was despawned since the physics snapshot", body.entity);
+                    continue;
+                }
+            };
+            set(&mut e, &body.transform);
+            set(&mut e, &body.interpolation);
+            set(&mut e, &body.collider);
+            let (rigid_body, fixed, kinematic, target) = &body.kind;
+            set(&mut e, rigid_body);
+            set(&mut e, fixed);
+            set(&mut e, kinematic);
+            set(&mut e, target);
+            let (lin_vel, ang_vel) = &body.velocity;
+            set(&mut e, lin_vel);
+            set(&mut e, ang_vel);
+            let (mass, inv_mass, density, com, com_world, inertia, inv_inertia) = &body.mass;
+            set(&mut e, mass);
+            set(&mut e, inv_mass);
+            set(&mut e, density);
+            set(&mut e, com);
+            set(&mut e, com_world);
+            set(&mut e, inertia);
+            set(&mut e, inv_inertia);
+            let (elasticity, friction) = &body.material;
+            set(&mut e, elasticity);
+            set(&mut e, friction);
+            let (force, torque, damping, gravity_scale, locked) = &body.forces;
+            set(&mut e, force);
+            set(&mut e, torque);
+            set(&mut e, damping);
+            set(&mut e, gravity_scale);
+            set(&mut e, locked);
+            let (sleep_state, sleeping) = &body.sleep;
+            set(&mut e, sleep_state);
+            set(&mut e, sleeping);
+            let (groups, sensor, ccd, events) = &body.collision;
+            set(&mut e, groups);
+            set(&mut e, sensor);
+            set(&mut e, ccd);
+            set(&mut e, events);
+            // a changed joint wakes its bodies, so it is only written if it was
+            match (&body.joint, e.get::<Joint>()) {
+                (Some(joint), Some(current)) if joint == current => {}
+                (joint, _) => set(&mut e, joint),
+            }
+        }
+
+        world.insert_resource(self.timestep.clone());
+        world.insert_resource(self.time);
+        world.insert_resource(self.broad_phase.clone());
+        world.insert_resource(self.contacts.clone());
+        world.insert_resource(self.colliding.clone());
+
+        // the restored broad phase doesn't hold them, marked changed they are put back in
+        for e in uncaptured.iter() {
+            if let Some(mut collider) = world.get_mut::<Collider>(*e) {
+                collider.set_changed();
+            }
+        }
+        uncaptured
+    }
+}
+
+// Writes the captured value over the current one, or removes the component if there was none
+fn set<T: Component + Clone>(e: &mut EntityMut, value: &Option<T>) {
+    match value {
+        Some(value) => match e.get_mut::<T>() {
+            Some(mut current) => *current = value.clone(),
+            None => {
+                e.insert(value.clone());
+            }
+        },
+        None => {
+            e.remove::<T>();
+        }
+    }
+}
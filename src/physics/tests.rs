@@ -0,0 +1,523 @@
+use bevy::{
+    ecs::{
+        event::{Events, ManualEventReader},
+        system::{Resource, SystemState},
+    },
+    prelude::*,
+};
+
+use super::*;
+
+const STEP: f32 = 1.0 / 60.0;
+
+// The physics on its own, stepped by hand at a fixed rate so frame time plays no part.
+// Shared with the unit tests of the other physics modules
+pub(super) fn app() -> App {
+    app_with(BroadPhaseKind::default())
+}
+
+fn app_with(broad_phase: BroadPhaseKind) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(PhysicsPlugin { broad_phase });
+    let mut timestep = app.world.resource_mut::<PhysicsTimestep>();
+    *timestep = PhysicsTimestep::new(STEP);
+    timestep.time_scale = 0.0;
+    // transforms are read straight after each step
+    timestep.interpolate = false;
+    app
+}
+
+pub(super) fn step(app: &mut App) {
+    app.world.resource_mut::<PhysicsTimestep>().advance(STEP);
+    app.update();
+}
+
+fn run(app: &mut App, seconds: f32) {
+    for _ in 0..(seconds / STEP).round() as usize {
+        step(app);
+    }
+}
+
+// Static ground with its top at y = 0
+fn floor(app: &mut App, elasticity: f32, friction: f32) -> Entity {
+    app.world
+        .spawn()
+        .insert(Transform::from_xyz(0.0, -0.5, 0.0))
+        .insert(Collider::cuboid(50.0, 0.5, 50.0))
+        .insert(Static)
+        .insert(Elasticity(elasticity))
+        .insert(Friction(friction))
+        .id()
+}
+
+fn gravity(app: &App) -> f32 {
+    -app.world.resource::<Gravity>().0.y
+}
+
+fn translation(app: &App, e: Entity) -> Vec3 {
+    app.world.get::<Transform>(e).unwrap().translation
+}
+
+fn velocity(app: &App, e: Entity) -> Vec3 {
+    app.world.get::<LinearVelocity>(e).unwrap().0
+}
+
+// Events sent since the reader last looked, events only last two updates so read every step
+fn read<E: Resource + Clone>(app: &App, reader: &mut ManualEventReader<E>) -> Vec<E> {
+    reader.iter(app.world.resource::<Events<E>>()).cloned().collect()
+}
+
+#[test]
+fn free_fall_matches_analytic_motion() {
+    let mut app = app();
+    let (start, launch) = (Vec3::new(0.0, 10.0, 0.0), Vec3::new(1.0, 5.0, -2.0));
+    let ball = app
+        .world
+        .spawn()
+        .insert(Transform::from_translation(start))
+        .insert(Collider::sphere(0.5))
+        .insert(LinearVelocity(launch))
+        .id();
+
+    let g = gravity(&app);
+    for i in 1..=90 {
+        step(&mut app);
+        let t = i as f32 * STEP;
+        let expected = start + launch * t - Vec3::Y * 0.5 * g * t * t;
+        // semi implicit euler runs ahead of the parabola by half a step's worth of velocity
+        let tolerance = 0.5 * g * t * STEP + 1e-4;
+        let moved = translation(&app, ball);
+        assert!(
+            (moved - expected).length() <= tolerance,
+            "at {}s expected {:?} got {:?}",
+            t,
+            expected,
+            moved
+        );
+        let expected_velocity = launch - Vec3::Y * g * t;
+        assert!((velocity(&app, ball) - expected_velocity).length() < 1e-3);
+    }
+    assert_eq!(
+        app.world.get::<AngularVelocity>(ball).unwrap().0,
+        Vec3::ZERO
+    );
+}
+
+#[test]
+fn elastic_bounce_conserves_energy() {
+    let mut app = app();
+    floor(&mut app, 1.0, 0.0);
+    let radius = 0.5;
+    let drop = 3.0;
+    let ball = app
+        .world
+        .spawn()
+        .insert(Transform::from_xyz(0.0, drop, 0.0))
+        .insert(Collider::sphere(radius))
+        .insert(Elasticity(1.0))
+        .insert(Friction(0.0))
+        .id();
+
+    // energy per unit of mass, taken at the top of each bounce where it is all potential
+    let g = gravity(&app);
+    let energy = |height: f32, speed: f32| g * height + 0.5 * speed * speed;
+    let start = energy(drop, 0.0);
+    let mut apexes = Vec::new();
+    let mut rising = false;
+    for _ in 0..(6.0 / STEP) as usize {
+        step(&mut app);
+        let v = velocity(&app, ball);
+        if rising && v.y <= 0.0 {
+            apexes.push(energy(translation(&app, ball).y, v.length()));
+        }
+        rising = v.y > 0.0;
+    }
+
+    assert!(apexes.len() >= 3, "only bounced {} times", apexes.len());
+    for apex in apexes {
+        assert!(
+            (apex - start).abs() / start < 0.01,
+            "energy went from {} to {}",
+            start,
+            apex
+        );
+    }
+}
+
+#[test]
+fn box_stack_stays_stable() {
+    let mut app = app();
+    floor(&mut app, 0.0, 0.6);
+    let boxes = (0..5)
+        .map(|i| {
+            app.world
+                .spawn()
+                .insert(Transform::from_xyz(0.0, 0.5 + i as f32, 0.0))
+                .insert(Collider::cuboid(0.5, 0.5, 0.5))
+                .insert(Friction(0.6))
+                .id()
+        })
+        .collect::<Vec<_>>();
+
+    run(&mut app, 5.0);
+
+    // each contact is allowed to sink by about the slop, and the stack to lean a little
+    let slop = app.world.resource::<SolverSettings>().slop;
+    for (i, b) in boxes.iter().enumerate() {
+        let trans = app.world.get::<Transform>(*b).unwrap();
+        let sunk = 0.5 + i as f32 - trans.translation.y;
+        let sideways = trans.translation * Vec3::new(1.0, 0.0, 1.0);
+        assert!(
+            sunk >= 0.0 && sunk < 2.0 * slop * (i + 1) as f32,
+            "box {} sank by {}",
+            i,
+            sunk
+        );
+        assert!(sideways.length() < 0.1, "box {} slid to {:?}", i, sideways);
+        assert!(
+            (trans.rotation * Vec3::Y).dot(Vec3::Y) > 0.999,
+            "box {} tipped",
+            i
+        );
+        // settled enough to sleep
+        assert!(
+            app.world.get::<Sleeping>(*b).is_some(),
+            "box {} is still moving",
+            i
+        );
+    }
+}
+
+#[test]
+fn spinning_body_conserves_angular_momentum() {
+    let mut app = app();
+    app.insert_resource(Gravity(Vec3::ZERO));
+    // lopsided, so the spin axis wobbles and the gyroscopic term does real work
+    let body = app
+        .world
+        .spawn()
+        .insert(Transform::from_rotation(Quat::from_rotation_z(0.3)))
+        .insert(Collider::cuboid(1.0, 0.5, 0.25))
+        .insert(AngularVelocity(Vec3::new(0.3, 0.2, 6.0)))
+        .id();
+
+    let momentum = |app: &App| {
+        let rotation = Mat3::from_quat(app.world.get::<Transform>(body).unwrap().rotation);
+        let inertia = app.world.get::<InertiaTensor>(body).unwrap().0;
+        let spin = app.world.get::<AngularVelocity>(body).unwrap().0;
+        rotation * inertia * rotation.transpose() * spin
+    };
+
+    step(&mut app);
+    let start = momentum(&app);
+    for _ in 0..(3.0 / STEP) as usize {
+        step(&mut app);
+        let now = momentum(&app);
+        assert!(
+            (now - start).length() / start.length() < 0.01,
+            "angular momentum went from {:?} to {:?}",
+            start,
+            now
+        );
+    }
+    assert!(app.world.get::<Sleeping>(body).is_none());
+}
+
+#[test]
+fn friction_stops_a_sliding_box() {
+    let mut app = app();
+    let friction = 0.5;
+    floor(&mut app, 0.0, friction);
+    let speed = 5.0;
+    let b = app
+        .world
+        .spawn()
+        .insert(Transform::from_xyz(0.0, 0.5, 0.0))
+        .insert(Collider::cuboid(0.5, 0.5, 0.5))
+        .insert(Friction(friction))
+        .insert(LinearVelocity(Vec3::X * speed))
+        .id();
+
+    // friction coefficients of the two bodies multiply
+    let g = gravity(&app);
+    let mu = friction * friction;
+    let stopping_time = speed / (mu * g);
+    let stopping_distance = speed * speed / (2.0 * mu * g);
+
+    run(&mut app, stopping_time * 0.5);
+    let halfway = velocity(&app, b).x;
+    assert!(
+        (halfway - speed * 0.5).abs() < 0.25,
+        "expected {} halfway, moving at {}",
+        speed * 0.5,
+        halfway
+    );
+
+    run(&mut app, stopping_time);
+    assert!(velocity(&app, b).length() < 0.01);
+    let travelled = translation(&app, b).x;
+    assert!(
+        (travelled - stopping_distance).abs() / stopping_distance < 0.05,
+        "slid {} instead of {}",
+        travelled,
+        stopping_distance
+    );
+    let up = app.world.get::<Transform>(b).unwrap().rotation * Vec3::Y;
+    assert!(up.dot(Vec3::Y) > 0.999);
+}
+
+#[test]
+fn massless_body_is_not_moved() {
+    let mut app = app();
+    let start = Vec3::new(0.0, 5.0, 0.0);
+    let body = app
+        .world
+        .spawn()
+        .insert(Transform::from_translation(start))
+        .insert(Collider::sphere(0.5))
+        .insert(Mass(0.0))
+        .id();
+
+    run(&mut app, 1.0);
+    assert_eq!(translation(&app, body), start);
+    assert!(app.world.get::<AngularVelocity>(body).unwrap().0.is_finite());
+}
+
+#[test]
+fn massless_bodies_hold_up_dynamic_ones() {
+    let mut app = app();
+    // neither is static, the plane has no mass by shape and the block is given none
+    let ground = app
+        .world
+        .spawn()
+        .insert(Transform::default())
+        .insert(Collider::plane(Vec3::Y))
+        .id();
+    let block = app
+        .world
+        .spawn()
+        .insert(Transform::from_xyz(5.0, 0.5, 0.0))
+        .insert(Collider::cuboid(0.5, 0.5, 0.5))
+        .insert(Mass(0.0))
+        .id();
+    let on_ground = app
+        .world
+        .spawn()
+        .insert(Transform::from_xyz(0.0, 2.0, 0.0))
+        .insert(Collider::cuboid(0.5, 0.5, 0.5))
+        .id();
+    let on_block = app
+        .world
+        .spawn()
+        .insert(Transform::from_xyz(5.0, 3.0, 0.0))
+        .insert(Collider::cuboid(0.5, 0.5, 0.5))
+        .id();
+
+    run(&mut app, 3.0);
+    for e in [ground, block, on_ground, on_block] {
+        let trans = app.world.get::<Transform>(e).unwrap();
+        assert!(
+            trans.translation.is_finite() && trans.rotation.is_finite(),
+            "{:?} ended up at {:?}",
+            e,
+            trans
+        );
+        assert!(velocity(&app, e).is_finite());
+        assert!(app.world.get::<AngularVelocity>(e).unwrap().0.is_finite());
+    }
+    assert_eq!(translation(&app, ground), Vec3::ZERO);
+    assert_eq!(translation(&app, block), Vec3::new(5.0, 0.5, 0.0));
+    let slop = app.world.resource::<SolverSettings>().slop;
+    assert!((translation(&app, on_ground).y - 0.5).abs() < 2.0 * slop);
+    assert!((translation(&app, on_block).y - 1.5).abs() < 2.0 * slop);
+}
+
+#[test]
+fn impulse_at_point_pushes_and_turns() {
+    let mut app = app();
+    app.insert_resource(Gravity(Vec3::ZERO));
+    let body = app
+        .world
+        .spawn()
+        .insert(Transform::default())
+        .insert(Collider::cuboid(0.5, 0.5, 0.5))
+        .insert(Mass(2.0))
+        .id();
+    let empty = app.world.spawn().id();
+    step(&mut app);
+
+    // hit on the top edge, sideways
+    let (impulse, point) = (Vec3::X * 4.0, Vec3::Y * 0.5);
+    let mut state: SystemState<Impulses> = SystemState::new(&mut app.world);
+    let mut impulses = state.get_mut(&mut app.world);
+    assert!(impulses.apply_impulse_at_point(body, impulse, point));
+    assert!(!impulses.apply_impulse_at_point(empty, impulse, point));
+
+    let inertia = app.world.get::<InertiaTensor>(body).unwrap().0;
+    assert_eq!(velocity(&app, body), impulse / 2.0);
+    let spin = app.world.get::<AngularVelocity>(body).unwrap().0;
+    let expected = inertia.inverse() * point.cross(impulse);
+    assert!((spin - expected).length() < 1e-5, "spinning at {:?}", spin);
+    assert!(spin.z < 0.0);
+}
+
+#[test]
+fn collision_ends_when_the_listener_despawns() {
+    let mut app = app();
+    let ground = floor(&mut app, 0.0, 0.5);
+    let ball = app
+        .world
+        .spawn()
+        .insert(Transform::from_xyz(0.0, 0.49, 0.0))
+        .insert(Collider::sphere(0.5))
+        .insert(Elasticity(0.0))
+        .insert(CollisionEvents)
+        .id();
+    let pair = if ball < ground { (ball, ground) } else { (ground, ball) };
+    let mut started_reader = app.world.resource::<Events<CollisionStarted>>().get_reader();
+    let mut ended_reader = app.world.resource::<Events<CollisionEnded>>().get_reader();
+
+    let (mut started, mut ended) = (Vec::new(), Vec::new());
+    for _ in 0..60 {
+        step(&mut app);
+        started.extend(read(&app, &mut started_reader));
+        ended.extend(read(&app, &mut ended_reader));
+    }
+    assert_eq!(started.len(), 1);
+    assert_eq!((started[0].0, started[0].1), pair);
+    assert!(ended.is_empty());
+
+    // only the ball listens, the pair still ends once it is gone
+    app.world.despawn(ball);
+    step(&mut app);
+    let ended = read(&app, &mut ended_reader);
+    assert_eq!(ended.len(), 1);
+    assert_eq!((ended[0].0, ended[0].1), pair);
+    assert!(!app.world.resource::<CollidingPairs>().contains(ball, ground));
+}
+
+// Every body's pose and velocities as raw bits, so any difference at all shows up
+fn body_states(app: &mut App) -> Vec<(Entity, Vec<u32>)> {
+    let mut states = app
+        .world
+        .query::<(Entity, &Transform, &LinearVelocity, &AngularVelocity)>()
+        .iter(&app.world)
+        .map(|(e, trans, lin_vel, ang_vel)| {
+            let bits = [
+                trans.translation.to_array(),
+                lin_vel.0.to_array(),
+                ang_vel.0.to_array(),
+            ]
+            .iter()
+            .flatten()
+            .chain(trans.rotation.to_array().iter())
+            .map(|f| f.to_bits())
+            .collect();
+            (e, bits)
+        })
+        .collect::<Vec<_>>();
+    states.sort_by_key(|(e, _)| *e);
+    states
+}
+
+#[test]
+fn snapshot_replays_exactly() {
+    for broad_phase in [BroadPhaseKind::Bvh, BroadPhaseKind::SweepAndPrune] {
+        let mut app = app_with(broad_phase);
+        floor(&mut app, 0.2, 0.6);
+        // a leaning stack that topples, a ball rolling into it and a box off on its own to sleep
+        for i in 0..5 {
+            app.world
+                .spawn()
+                .insert(
+                    Transform::from_xyz(0.1 * i as f32, 0.5 + 1.01 * i as f32, 0.0)
+                        .with_rotation(Quat::from_rotation_y(0.2 * i as f32)),
+                )
+                .insert(Collider::cuboid(0.5, 0.5, 0.5));
+        }
+        app.world
+            .spawn()
+            .insert(Transform::from_xyz(0.0, 0.5, 8.0))
+            .insert(Collider::cuboid(0.5, 0.5, 0.5));
+        app.world
+            .spawn()
+            .insert(Transform::from_xyz(-4.0, 0.4, 0.0))
+            .insert(Collider::sphere(0.4))
+            .insert(LinearVelocity(Vec3::X * 6.0));
+        let anchor = app
+            .world
+            .spawn()
+            .insert(Transform::from_xyz(3.0, 5.0, 0.0))
+            .insert(Collider::sphere(0.1))
+            .insert(Static)
+            .id();
+        let bob = app
+            .world
+            .spawn()
+            .insert(Transform::from_xyz(5.0, 5.0, 0.0))
+            .insert(Collider::sphere(0.3))
+            .id();
+        app.world.spawn().insert(Joint::rope(anchor, bob, 2.0));
+
+        run(&mut app, 0.5);
+        let snapshot = PhysicsSnapshot::capture(&mut app.world);
+        let mut recorded = Vec::new();
+        for _ in 0..300 {
+            step(&mut app);
+            recorded.push(body_states(&mut app));
+        }
+
+        // knock things about and add a body, none of which was there at the capture
+        app.world.entity_mut(bob).insert(LinearVelocity(Vec3::Y * 20.0));
+        let late = app
+            .world
+            .spawn()
+            .insert(Transform::from_xyz(0.0, 3.0, 0.0))
+            .insert(Collider::sphere(0.5))
+            .id();
+        step(&mut app);
+
+        // an exact replay needs the late body gone, restore leaves that to the caller
+        assert_eq!(snapshot.restore(&mut app.world), vec![late]);
+        app.world.despawn(late);
+        for (i, expected) in recorded.iter().enumerate() {
+            step(&mut app);
+            assert!(
+                body_states(&mut app) == *expected,
+                "{:?} replay diverged at step {}",
+                broad_phase,
+                i
+            );
+        }
+    }
+}
+
+#[test]
+fn snapshot_leaves_uncaptured_entities() {
+    let mut app = app();
+    floor(&mut app, 0.0, 0.6);
+    step(&mut app);
+    let snapshot = PhysicsSnapshot::capture(&mut app.world);
+
+    // gameplay spawns a ball with something hanging off it after the capture
+    let mut ball = app.world.spawn();
+    ball.insert(Transform::from_xyz(0.0, 2.0, 0.0))
+        .insert(Collider::sphere(0.5));
+    let ball = ball.id();
+    let child = app.world.spawn().id();
+    app.world.entity_mut(ball).push_children(&[child]);
+    // until it has settled and stopped moving
+    run(&mut app, 3.0);
+    assert!(app.world.get::<Sleeping>(ball).is_some());
+
+    assert_eq!(snapshot.restore(&mut app.world), vec![ball]);
+    assert!(app.world.get_entity(ball).is_some());
+    assert!(app.world.get_entity(child).is_some());
+    // the restored broad phase didn't hold it, it has to be put back without having moved
+    step(&mut app);
+    let at = Transform::from_translation(translation(&app, ball));
+    let bounds = Collider::sphere(0.5).aabb(&at);
+    let broad_phase = app.world.resource::<BroadPhase>();
+    assert!(broad_phase.strategy.query(&bounds).contains(&ball));
+}
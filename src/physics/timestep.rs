@@ -0,0 +1,141 @@
+use bevy::{ecs::schedule::ShouldRun, prelude::*};
+
+use super::{PhysicsTime, Sleeping};
+
+#[derive(StageLabel, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PhysicsStage {
+    /// Once a frame, banks frame time and puts bodies back to their simulated transform
+    Prepare,
+    /// Runs once per fixed step owed, zero or more times a frame
+    Step,
+    /// Once a frame, blends rendered transforms between the last two steps
+    Interpolate,
+}
+
+#[derive(Debug, Clone)]
+pub struct PhysicsTimestep {
+    /// Seconds simulated per step
+    pub step: f32,
+    /// Steps allowed in one frame, time past this is dropped so a slow frame can't snowball
+    pub max_substeps: u32,
+    /// Multiplier on frame time, 0 pauses the simulation
+    pub time_scale: f32,
+    /// Blend rendered transforms between steps, otherwise bodies snap to each step
+    pub interpolate: bool,
+    accumulator: f32,
+}
+
+impl Default for PhysicsTimestep {
+    fn default() -> Self {
+        Self::new(1.0 / 60.0)
+    }
+}
+
+impl PhysicsTimestep {
+    pub fn new(step: f32) -> Self {
+        Self {
+            step,
+            max_substeps: 5,
+            time_scale: 1.0,
+            interpolate: true,
+            accumulator: 0.0,
+        }
+    }
+
+    /// Steps per second
+    pub fn rate(&self) -> f32 {
+        self.step.recip()
+    }
+
+    /// Banks simulation time, the steps run on the next update
+    pub fn advance(&mut self, seconds: f32) {
+        let max = self.step * self.max_substeps as f32;
+        self.accumulator = (self.accumulator + seconds).min(max);
+    }
+
+    /// Steps still owed this frame, not counting one being run
+    pub fn steps_due(&self) -> u32 {
+        if self.step > 0.0 {
+            (self.accumulator / self.step) as u32
+        } else {
+            0
+        }
+    }
+
+    /// How far [0,1) rendering is between the last step and the next one
+    pub fn alpha(&self) -> f32 {
+        if self.step > 0.0 {
+            (self.accumulator / self.step).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Simulated transforms either side of the current frame, rendered as a blend of the two
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct TransformInterpolation {
+    pub previous: Transform,
+    pub current: Transform,
+    // what was last written for rendering, anything else means the transform was moved by hand
+    rendered: Option<Transform>,
+}
+
+pub fn accumulate_time_system(
+    time: Res<Time>,
+    mut timestep: ResMut<PhysicsTimestep>,
+    mut pt: ResMut<PhysicsTime>,
+) {
+    let seconds = time.delta_seconds() * timestep.time_scale;
+    timestep.advance(seconds);
+    pt.time = timestep.step;
+}
+
+// Keeps the step stage looping while there is a step's worth of time banked
+pub fn physics_step_criteria(mut timestep: ResMut<PhysicsTimestep>) -> ShouldRun {
+    if timestep.step > 0.0 && timestep.accumulator >= timestep.step {
+        timestep.accumulator -= timestep.step;
+        ShouldRun::YesAndCheckAgain
+    } else {
+        ShouldRun::No
+    }
+}
+
+// Swaps the rendered blend back out for the real simulated transform
+pub fn restore_transform_system(
+    mut query: Query<(&mut Transform, &mut TransformInterpolation), Without<Sleeping>>,
+) {
+    for (mut trans, mut interpolation) in query.iter_mut() {
+        if interpolation.rendered == Some(*trans) {
+            *trans = interpolation.current;
+        } else {
+            // spawned or teleported, start from where it was put
+            interpolation.previous = *trans;
+            interpolation.current = *trans;
+        }
+    }
+}
+
+pub fn record_previous_transform_system(
+    mut query: Query<(&Transform, &mut TransformInterpolation), Without<Sleeping>>,
+) {
+    for (trans, mut interpolation) in query.iter_mut() {
+        interpolation.previous = *trans;
+    }
+}
+
+pub fn interpolate_transform_system(
+    timestep: Res<PhysicsTimestep>,
+    mut query: Query<(&mut Transform, &mut TransformInterpolation), Without<Sleeping>>,
+) {
+    let alpha = timestep.alpha();
+    for (mut trans, mut interpolation) in query.iter_mut() {
+        interpolation.current = *trans;
+        if timestep.interpolate {
+            let previous = interpolation.previous;
+            trans.translation = previous.translation.lerp(trans.translation, alpha);
+            trans.rotation = previous.rotation.slerp(trans.rotation, alpha);
+        }
+        interpolation.rendered = Some(*trans);
+    }
+}
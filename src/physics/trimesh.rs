@@ -0,0 +1,168 @@
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        render_resource::PrimitiveTopology,
+    },
+};
+
+use super::{Aabb, Bvh};
+
+/// Triangle soup in the local space of the collider, with a tree over the triangles so
+/// only the few near another shape are tested. It has no inside, so only suits static bodies
+#[derive(Debug, Clone)]
+pub struct TriMesh {
+    vertices: Vec<Vec3>,
+    triangles: Vec<[u32; 3]>,
+    // keyed by triangle index
+    bvh: Bvh,
+    bounds: Aabb,
+}
+
+impl TriMesh {
+    pub fn new(vertices: Vec<Vec3>, triangles: Vec<[u32; 3]>) -> Self {
+        let bounds = vertices
+            .iter()
+            .fold(None, |bounds: Option<Aabb>, v| {
+                let point = Aabb::new(*v, *v);
+                Some(bounds.map_or(point, |b| b.union(&point)))
+            })
+            .unwrap_or_else(|| Aabb::new(Vec3::ZERO, Vec3::ZERO));
+        let bvh = Bvh::build(
+            0.0,
+            triangles.iter().enumerate().map(|(i, tri)| {
+                let [a, b, c] = tri.map(|v| vertices[v as usize]);
+                (
+                    Entity::from_raw(i as u32),
+                    Aabb::new(a.min(b).min(c), a.max(b).max(c)),
+                )
+            }),
+        );
+        Self {
+            vertices,
+            triangles,
+            bvh,
+            bounds,
+        }
+    }
+
+    /// Triangles of a triangle list mesh, None for other topologies
+    pub fn from_mesh(mesh: &Mesh) -> Option<Self> {
+        if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+            return None;
+        }
+        let vertices = mesh_positions(mesh)?;
+        let indices = match mesh.indices() {
+            Some(Indices::U16(indices)) => indices.iter().map(|i| *i as u32).collect(),
+            Some(Indices::U32(indices)) => indices.clone(),
+            None => (0..vertices.len() as u32).collect(),
+        };
+        let triangles = indices
+            .chunks_exact(3)
+            .map(|tri| [tri[0], tri[1], tri[2]])
+            .collect();
+        Some(Self::new(vertices, triangles))
+    }
+
+    pub fn vertices(&self) -> &[Vec3] {
+        &self.vertices
+    }
+
+    pub fn triangles(&self) -> &[[u32; 3]] {
+        &self.triangles
+    }
+
+    /// Bounds of every vertex, in local space
+    pub fn bounds(&self) -> Aabb {
+        self.bounds
+    }
+
+    pub(crate) fn triangle(&self, i: usize) -> [Vec3; 3] {
+        self.triangles[i].map(|v| self.vertices[v as usize])
+    }
+
+    /// Triangles whose bounds overlap a box in local space
+    pub(crate) fn query(&self, aabb: &Aabb) -> impl Iterator<Item = usize> {
+        self.bvh.query(aabb).into_iter().map(|e| e.id() as usize)
+    }
+
+    /// Triangles whose bounds a ray in local space passes through before max_toi
+    pub(crate) fn query_ray(
+        &self,
+        origin: Vec3,
+        dir: Vec3,
+        max_toi: f32,
+    ) -> impl Iterator<Item = usize> {
+        self.bvh
+            .query_ray(origin, dir, max_toi)
+            .into_iter()
+            .map(|e| e.id() as usize)
+    }
+}
+
+/// Vertex positions of a mesh
+pub(crate) fn mesh_positions(mesh: &Mesh) -> Option<Vec<Vec3>> {
+    match mesh.attribute(Mesh::ATTRIBUTE_POSITION)? {
+        VertexAttributeValues::Float32x3(positions) => {
+            Some(positions.iter().map(|p| Vec3::from(*p)).collect())
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mesh(positions: Vec<[f32; 3]>, indices: Option<Indices>) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.set_indices(indices);
+        mesh
+    }
+
+    fn quad() -> Vec<[f32; 3]> {
+        vec![
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 0.0, -1.0],
+            [0.0, 0.0, -1.0],
+        ]
+    }
+
+    #[test]
+    fn from_cube_mesh() {
+        let cube = Mesh::from(shape::Cube { size: 2.0 });
+        let mesh = TriMesh::from_mesh(&cube).unwrap();
+        assert_eq!(mesh.triangles().len(), 12);
+        assert_eq!(mesh.vertices().len(), 24);
+        assert_eq!(mesh.bounds(), Aabb::new(-Vec3::ONE, Vec3::ONE));
+        // every triangle faces out of the cube
+        for i in 0..mesh.triangles().len() {
+            let [a, b, c] = mesh.triangle(i);
+            let normal = (b - a).cross(c - a);
+            assert!(normal.dot(a + b + c) > 0.0, "triangle {} faces in", i);
+        }
+    }
+
+    #[test]
+    fn from_mesh_indices() {
+        let u16 = mesh(quad(), Some(Indices::U16(vec![0, 1, 2, 0, 2, 3])));
+        let u32 = mesh(quad(), Some(Indices::U32(vec![0, 1, 2, 0, 2, 3])));
+        for mesh in [u16, u32] {
+            let mesh = TriMesh::from_mesh(&mesh).unwrap();
+            assert_eq!(mesh.triangles(), &[[0, 1, 2], [0, 2, 3]]);
+            assert_eq!(mesh.triangle(1), [Vec3::ZERO, Vec3::new(1.0, 0.0, -1.0), -Vec3::Z]);
+        }
+
+        // without indices every three vertices are a triangle
+        let mut positions = quad();
+        positions.extend(quad());
+        let mesh = TriMesh::from_mesh(&mesh(positions, None)).unwrap();
+        assert_eq!(mesh.triangles(), &[[0, 1, 2], [3, 4, 5]]);
+
+        let mut lines = Mesh::new(PrimitiveTopology::LineList);
+        lines.insert_attribute(Mesh::ATTRIBUTE_POSITION, quad());
+        assert!(TriMesh::from_mesh(&lines).is_none());
+    }
+}
@@ -123,15 +123,16 @@ impl Plugin for BreakoutPlugin {
                 SystemSet::on_update(GameState::Breakout)
                     //.with_system(update_ball)
                     .with_system(brick_collisions)
-                    //.with_system(bottom_collisions)
+                    .with_system(bottom_collisions)
                     .with_system(player_movement)
                     
                     // Old
                     //.with_system(ball_collision)
-                    //.with_system(ball_bounds_check),
             )
             .add_system_set(
-                SystemSet::on_exit(GameState::Breakout).with_system(cleanup_system::<Breakout>),
+                SystemSet::on_exit(GameState::Breakout)
+                    .with_system(cleanup_system::<Breakout>)
+                    .with_system(reset_physics),
             );;
 
     }
@@ -141,7 +142,8 @@ fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    //mut rapier_config: ResMut<RapierConfiguration>,
+    mut gravity: ResMut<Gravity>,
+    mut mode: ResMut<PhysicsMode>,
     mut score: ResMut<Score>,
     mut config: ResMut<BreakoutConfig>,
     mut ambient_light: ResMut<AmbientLight>,
@@ -149,7 +151,8 @@ fn setup(
     mut clear_color: ResMut<ClearColor>,
 ) {
     clear_color.0 = Color::rgb(0.2, 0.2, 0.2);
-    //rapier_config.gravity = Vec2::ZERO.into();
+    gravity.0 = Vec3::ZERO;
+    *mode = PhysicsMode::TwoD;
     score.0 = 0;
 
     // camera
@@ -212,7 +215,7 @@ fn setup(
         ),
         &breakout_assets,
     );
-    commands.entity(bottom).insert(Bottom);
+    commands.entity(bottom).insert(Bottom).insert(Sensor);
 
     let size_x: f32 = config.board_size_half.x / (config.brick_grid.0 + 2) as f32;
     let size_y: f32 = config.board_size_half.y * 0.5 / (config.brick_grid.1 + 2) as f32;
@@ -245,11 +248,11 @@ fn setup(
                     transform: Transform::from_xyz(pos_x, pos_y, 0.0),
                     ..default()
                 })
-                //.insert(RigidBody::Fixed)
+                .insert(Static)
                 .insert(Collider::cuboid(size_x * 0.5, size_y * 0.5, 1.0))
                 //.insert(ColliderMassProperties::Density(2.0))
-                //.insert(Friction::coefficient(0.0))
-                //.insert(Restitution::coefficient(1.0))
+                .insert(Friction(0.0))
+                .insert(Elasticity(1.0))
                 .insert(Brick)
                 .insert(Name::new(format!("Brick {}x{}", x, y)))
                 .insert(Breakout);
@@ -274,11 +277,11 @@ fn create_board_side(
             transform: Transform::from_xyz(pos.x, pos.y, 0.0),
             ..default()
         })
-        //.insert(RigidBody::Fixed)
+        .insert(Static)
         //.insert(ColliderMassProperties::Density(2.0))
         .insert(Collider::cuboid(size_half.x, size_half.y, 1.0))
-        //.insert(Friction::coefficient(0.0))
-        //.insert(Restitution::coefficient(1.0))
+        .insert(Friction(0.0))
+        .insert(Elasticity(1.0))
         .insert(Breakout)
         .insert(Name::new("Board Side"))
         .id()
@@ -304,14 +307,14 @@ fn spawn_player(
             ),
             ..default()
         })
-        //.insert(RigidBody::KinematicPositionBased)
+        .insert(Kinematic::PositionBased)
         .insert(Collider::cuboid(
             config.player_size_half.x,
             config.player_size_half.y,
          1.0))
         //.insert(ColliderMassProperties::Density(2.0))
-        //.insert(Friction::coefficient(0.0))
-        //.insert(Restitution::coefficient(1.0))
+        .insert(Friction(0.0))
+        .insert(Elasticity(1.0))
         .insert(Player { index: 0 })
         .insert(Name::new("Player"))
         .insert(Breakout);
@@ -346,8 +349,9 @@ fn spawn_ball(
             0.0)))
         .insert(Friction(0.0))
         .insert(Elasticity(1.0))
-        //.insert(ActiveEvents::COLLISION_EVENTS)
-        //.insert(LockedAxes::ROTATION_LOCKED)
+        .insert(CollisionEvents)
+        .insert(Ccd)
+        .insert(LockedAxes::ROTATION_LOCKED)
         .insert(Collider::sphere(config.ball_size_half))
         //.insert(ColliderMassProperties::Density(2.0))
         .insert(Ball)
@@ -377,64 +381,53 @@ fn update_ball(mut balls: Query<&mut LinearVelocity, With<Ball>>, config: Res<Br
     }
 }
 
-// The ball can get away using the paddle to force it though a wall, this checks for that
-// fn ball_bounds_check(
-//     balls: Query<&RigidBodyPosition, With<Ball>>,
-//     config: Res<BreakoutConfig>,
-//     mut state: ResMut<State<BreakoutState>>,
-// ) {
-//     for rb_pos in balls.iter() {
-//         if rb_pos.position.translation.x.abs() > config.board_size_half.x
-//             || rb_pos.position.translation.y.abs() > config.board_size_half.y
-//         {
-//             state.set(BreakoutState::Resetting).unwrap()
-//         }
-//     }
-// }
+// The rest of the game expects normal gravity in 3D
+fn reset_physics(mut gravity: ResMut<Gravity>, mut mode: ResMut<PhysicsMode>) {
+    *gravity = Gravity::default();
+    *mode = PhysicsMode::default();
+}
 
 /* A system that displays the events. */
 fn brick_collisions(
     mut commands: Commands,
-   // mut collision_events: EventReader<CollisionEvent>,
+    mut collision_events: EventReader<CollisionEnded>,
     bricks: Query<Entity, With<Brick>>,
     mut score: ResMut<Score>,
     mut state: ResMut<State<BreakoutState>>,
 ) {
-    // for e in collision_events.iter() {
-    //     info!("Collision event: {:?}", e);
-    //     if let CollisionEvent::Stopped(a, b, _) = e {
-    //         // remove bricks
-    //         if let Ok(bricks) = bricks.get(*a) {
-    //             commands.entity(*a).despawn_recursive();
-    //             score.0 += 1;
-    //         }
-    //         if let Ok(bricks) = bricks.get(*b) {
-    //             commands.entity(*b).despawn_recursive();
-    //             score.0 += 1;
-    //         }
-    //     }
-    // }
+    for e in collision_events.iter() {
+        debug!("Collision event: {:?}", e);
+        let CollisionEnded(a, b) = e;
+        // remove bricks
+        if let Ok(bricks) = bricks.get(*a) {
+            commands.entity(*a).despawn_recursive();
+            score.0 += 1;
+        }
+        if let Ok(bricks) = bricks.get(*b) {
+            commands.entity(*b).despawn_recursive();
+            score.0 += 1;
+        }
+    }
 }
 
 fn bottom_collisions(
     mut commands: Commands,
-    //mut collision_events: EventReader<CollisionEvent>,
+    mut collision_events: EventReader<CollisionStarted>,
     bottom: Query<Entity, With<Bottom>>,
     mut state: ResMut<State<BreakoutState>>,
 ) {
-    // for e in collision_events.iter() {
-    //     if let CollisionEvent::Stopped(a, b, _) = e {
-    //         // remove ball and restart
-    //         if let Ok(c) = bottom.get(*a) {
-    //             commands.entity(*b).despawn_recursive();
-    //             state.set(BreakoutState::Resetting).unwrap();
-    //         }
-    //         if let Ok(c) = bottom.get(*b) {
-    //             commands.entity(*a).despawn_recursive();
-    //             state.set(BreakoutState::Resetting).unwrap();
-    //         }
-    //     }
-    // }
+    for e in collision_events.iter() {
+        let CollisionStarted(a, b, _) = e;
+        // remove ball and restart, ignoring a reset already under way
+        if let Ok(c) = bottom.get(*a) {
+            commands.entity(*b).despawn_recursive();
+            let _ = state.set(BreakoutState::Resetting);
+        }
+        if let Ok(c) = bottom.get(*b) {
+            commands.entity(*a).despawn_recursive();
+            let _ = state.set(BreakoutState::Resetting);
+        }
+    }
 }
 
 fn other_keyboard_input(
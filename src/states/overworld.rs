@@ -19,6 +19,9 @@ impl Plugin for OverworldPlugin {
             SystemSet::on_enter(GameState::Overworld)
                 .with_system(setup_overworld)
                 .with_system(setup_sandbox),
+        ).add_system_set(
+            SystemSet::on_update(GameState::Overworld)
+                .with_system(sandbox_rewind_system.exclusive_system()),
         ).add_system_set(
             SystemSet::on_exit(GameState::Overworld).with_system(cleanup_system::<Overworld>),
         );
@@ -89,6 +92,26 @@ fn setup_overworld(
     //     .insert(Overworld);
 }
 
+// Physics state saved in the sandbox to rewind to
+struct SandboxSnapshot(PhysicsSnapshot);
+
+// F5 saves the sandbox physics, F9 rewinds to the last save
+fn sandbox_rewind_system(world: &mut World) {
+    let keys = world.resource::<Input<KeyCode>>();
+    let (save, rewind) = (keys.just_pressed(KeyCode::F5), keys.just_pressed(KeyCode::F9));
+    if save {
+        let snapshot = PhysicsSnapshot::capture(world);
+        world.insert_resource(SandboxSnapshot(snapshot));
+    } else if rewind && world.contains_resource::<SandboxSnapshot>() {
+        let uncaptured = world
+            .resource_scope(|world, snapshot: Mut<SandboxSnapshot>| snapshot.0.restore(world));
+        // the sandbox spawns nothing after setup, bodies it didn't save aren't its to remove
+        if !uncaptured.is_empty() {
+            debug!("Rewound the sandbox, {} unsaved bodies left as they are", uncaptured.len());
+        }
+    }
+}
+
 fn setup_sandbox(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -127,4 +150,104 @@ fn setup_sandbox(
         //.insert(ColliderMassProperties::Density(2.0))
         .insert(Name::new("Sphere"))
         .insert(Overworld);
+
+    let (half_height, capsule_r) = (0.4, 0.3);
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Capsule {
+                radius: capsule_r,
+                depth: 2.0 * half_height,
+                ..default()
+            })),
+            material: materials.add(StandardMaterial {
+                base_color: Color::rgb(0.8, 0.5, 0.2),
+                ..default()
+            }),
+            transform: Transform::from_xyz(-2.0, 3.0, 0.0)
+                .with_rotation(Quat::from_rotation_z(0.3)),
+            ..default()
+        })
+        .insert(RigidBody)
+        .insert(Collider::capsule(half_height, capsule_r))
+        .insert(Name::new("Capsule"))
+        .insert(Overworld);
+
+    // L shaped piece, one body with a collider on each brick
+    let brick_mesh = meshes.add(Mesh::from(shape::Cube { size: 0.5 }));
+    let brick_material = materials.add(StandardMaterial {
+        base_color: Color::rgb(0.2, 0.4, 0.8),
+        ..default()
+    });
+    let bricks = [
+        vec3(0.0, 0.0, 0.0),
+        vec3(0.5, 0.0, 0.0),
+        vec3(1.0, 0.0, 0.0),
+        vec3(1.0, 0.5, 0.0),
+    ];
+    commands
+        .spawn_bundle((
+            Transform::from_xyz(-1.0, 5.0, 1.0).with_rotation(Quat::from_rotation_x(0.4)),
+            GlobalTransform::default(),
+        ))
+        .insert(RigidBody)
+        .insert(Name::new("Piece"))
+        .insert(Overworld)
+        .with_children(|parent| {
+            for pos in bricks {
+                parent
+                    .spawn_bundle(PbrBundle {
+                        mesh: brick_mesh.clone(),
+                        material: brick_material.clone(),
+                        transform: Transform::from_translation(pos),
+                        ..default()
+                    })
+                    .insert(Collider::cuboid(0.25, 0.25, 0.25))
+                    .insert(Name::new("Brick"));
+            }
+        });
+
+    // chain hanging off a fixed post, laid out sideways so it swings
+    let link_r = 0.15;
+    let link_gap = 0.4;
+    let link_mesh = meshes.add(Mesh::from(shape::UVSphere {
+        radius: link_r,
+        ..default()
+    }));
+    let link_material = materials.add(StandardMaterial {
+        base_color: Color::rgb(0.6, 0.6, 0.6),
+        ..default()
+    });
+    let mut previous = commands
+        .spawn_bundle(PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Cube { size: 0.2 })),
+            material: link_material.clone(),
+            transform: Transform::from_xyz(2.0, 4.0, 0.0),
+            ..default()
+        })
+        .insert(Static)
+        .insert(Collider::cuboid(0.1, 0.1, 0.1))
+        .insert(Name::new("Chain Post"))
+        .insert(Overworld)
+        .id();
+    for i in 1..=8 {
+        let link = commands
+            .spawn_bundle(PbrBundle {
+                mesh: link_mesh.clone(),
+                material: link_material.clone(),
+                transform: Transform::from_xyz(2.0 + i as f32 * link_gap, 4.0, 0.0),
+                ..default()
+            })
+            .insert(Collider::sphere(link_r))
+            .insert(Name::new("Chain Link"))
+            .insert(Overworld)
+            .id();
+        commands
+            .spawn()
+            .insert(
+                Joint::ball(previous, link)
+                    .with_anchors(vec3(link_gap * 0.5, 0.0, 0.0), vec3(-link_gap * 0.5, 0.0, 0.0)),
+            )
+            .insert(Overworld);
+        previous = link;
+    }
 }
@@ -3,7 +3,7 @@ use std::f32::consts::PI;
 use bevy::{math::vec2, prelude::*, render::camera::Camera2d};
 use bevy_mod_picking::{PickingCameraBundle, PickableBundle, PickingEvent};
 
-use crate::{GameState, assets::{UiColors, UiFont}, ui::{self, create_button}, systems::{cleanup_system, CameraController}};
+use crate::{GameState, assets::{UiColors, UiFont}, csp::{AllDifferent, Domain, Problem, Solver}, ui::{self, create_button}, systems::{cleanup_system, CameraController}};
 
 pub struct SudokuPlugin;
 
@@ -15,7 +15,11 @@ impl Plugin for SudokuPlugin {
         app.init_resource::<SudokuState>()
             .init_resource::<SudokuAssets>()
             .add_system_set(SystemSet::on_enter(GameState::Sudoku).with_system(setup_sudoku))
-            .add_system_set(SystemSet::on_update(GameState::Sudoku).with_system(print_events))
+            .add_system_set(
+                SystemSet::on_update(GameState::Sudoku)
+                    .with_system(print_events)
+                    .with_system(solve_input),
+            )
             .add_system_set(SystemSet::on_exit(GameState::Sudoku).with_system(cleanup_system::<Sudoku>));
     }
 }
@@ -26,7 +30,7 @@ struct Position {
     y: u8,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
 enum Tile {
     Empty,
     Number(u8),
@@ -86,6 +90,66 @@ impl Default for SudokuState {
     }
 }
 
+impl SudokuState {
+    fn var(x: usize, y: usize) -> usize {
+        x * 9 + y
+    }
+
+    /// Sudoku rules as a constraint problem, one variable per cell with values 0..9 standing
+    /// for the numbers 1..=9
+    fn problem() -> Problem {
+        let mut problem = Problem::new();
+        for _ in 0..81 {
+            problem.add_variable(Domain::full(9));
+        }
+
+        for i in 0..9 {
+            // column and row
+            problem.add_constraint(AllDifferent::new((0..9).map(|j| Self::var(i, j)).collect()));
+            problem.add_constraint(AllDifferent::new((0..9).map(|j| Self::var(j, i)).collect()));
+
+            // 3x3 box
+            let (bx, by) = ((i % 3) * 3, (i / 3) * 3);
+            problem.add_constraint(AllDifferent::new(
+                (0..9).map(|j| Self::var(bx + j % 3, by + j / 3)).collect(),
+            ));
+        }
+        problem
+    }
+
+    /// Solver with the numbers on the board already placed
+    fn solver(&self) -> Solver {
+        let mut solver = Solver::new(Self::problem());
+        for x in 0..9 {
+            for y in 0..9 {
+                if let Tile::Number(n) = self.board[x][y] {
+                    if solver.assign(Self::var(x, y), (n - 1) as usize).is_err() {
+                        return solver;
+                    }
+                }
+            }
+        }
+        solver
+    }
+
+    /// Fill in the board, returns false if the givens have no solution
+    pub fn solve(&mut self) -> bool {
+        let mut solver = self.solver();
+        if !solver.solve(&mut rand::thread_rng()) {
+            return false;
+        }
+
+        let solution = solver.solution().unwrap();
+        for x in 0..9 {
+            for y in 0..9 {
+                self.board[x][y] = Tile::Number(solution[Self::var(x, y)] as u8 + 1);
+            }
+        }
+        self.solved = true;
+        true
+    }
+}
+
 fn setup_sudoku(
     mut commands: Commands,
     mut state: ResMut<SudokuState>,
@@ -275,4 +339,77 @@ fn print_events(
             _ => (),
         }
     }
-}
\ No newline at end of file
+}
+
+fn solve_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut state: ResMut<SudokuState>,
+    mut cells: Query<(&Position, &mut Handle<StandardMaterial>)>,
+    sudoku_assets: Res<SudokuAssets>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::Return) {
+        return;
+    }
+
+    if !state.solve() {
+        warn!("Sudoku has no solution");
+        return;
+    }
+
+    for (pos, mut material) in cells.iter_mut() {
+        *material = match state.board[pos.x as usize][pos.y as usize] {
+            Tile::Empty => sudoku_assets.empty.clone(),
+            Tile::Number(_) => sudoku_assets.red.clone(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solves_puzzle() {
+        let puzzle = [
+            "53..7....",
+            "6..195...",
+            ".98....6.",
+            "8...6...3",
+            "4..8.3..1",
+            "7...2...6",
+            ".6....28.",
+            "...419..5",
+            "....8..79",
+        ];
+
+        let mut state = SudokuState::default();
+        for (y, row) in puzzle.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if let Some(n) = c.to_digit(10) {
+                    state.board[x][y] = Tile::Number(n as u8);
+                }
+            }
+        }
+
+        assert!(state.solve());
+        assert_eq!(state.board[2][0], Tile::Number(4));
+        assert_eq!(state.board[8][8], Tile::Number(9));
+        for y in 0..9 {
+            let mut seen = [false; 9];
+            for x in 0..9 {
+                if let Tile::Number(n) = state.board[x][y] {
+                    assert!(!seen[n as usize - 1]);
+                    seen[n as usize - 1] = true;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_conflicting_givens() {
+        let mut state = SudokuState::default();
+        state.board[0][0] = Tile::Number(1);
+        state.board[1][0] = Tile::Number(1);
+        assert!(!state.solve());
+    }
+}
@@ -4,6 +4,8 @@ use bevy::{
     render::{primitives::Frustum, view::VisibleEntities},
 };
 
+use crate::physics::{Collider, Static};
+
 pub struct EnviromentPlugin;
 impl Plugin for EnviromentPlugin {
     fn build(&self, app: &mut App) {
@@ -69,6 +71,8 @@ fn create_ground(
                 transform: Transform::from_xyz(0.0, 0.0, 0.0),
                 ..Default::default()
             })
+            .insert(Static)
+            .insert(Collider::plane(Vec3::Y))
             .insert(Name::new("Ground"));
     }
 }
@@ -13,12 +13,26 @@ impl Tiles {
         vec![Tiles::Sand, Tiles::Grass, Tiles::Water, Tiles::Forest]
     }
 
+    pub fn index(&self) -> usize {
+        match self {
+            Tiles::Sand => 0,
+            Tiles::Grass => 1,
+            Tiles::Water => 2,
+            Tiles::Forest => 3,
+        }
+    }
+
+    pub fn from_index(index: usize) -> Tiles {
+        Tiles::values()[index]
+    }
+
+    // Every tile can sit next to itself, otherwise no grid with diagonals could be solved
     pub fn allowed_neighbors(&self) -> Vec<Tiles> {
         match self {
-            Tiles::Sand => vec![Tiles::Grass, Tiles::Water],
-            Tiles::Grass => vec![Tiles::Sand, Tiles::Forest],
-            Tiles::Water => vec![Tiles::Sand],
-            Tiles::Forest => vec![Tiles::Grass],
+            Tiles::Sand => vec![Tiles::Sand, Tiles::Grass, Tiles::Water],
+            Tiles::Grass => vec![Tiles::Grass, Tiles::Sand, Tiles::Forest],
+            Tiles::Water => vec![Tiles::Water, Tiles::Sand],
+            Tiles::Forest => vec![Tiles::Forest, Tiles::Grass],
         }
     }
 
@@ -35,3 +49,39 @@ impl Tiles {
         4
     }
 }
+
+/// Which tiles may sit next to each other
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub enum Ruleset {
+    /// Water, sand, grass and forest in bands
+    Classic,
+    /// Water can reach straight into grass and forest
+    Wetlands,
+    /// No rules, every tile can sit next to every other
+    Noise,
+}
+
+impl Default for Ruleset {
+    fn default() -> Self {
+        Ruleset::Classic
+    }
+}
+
+impl Ruleset {
+    pub fn values() -> Vec<Ruleset> {
+        vec![Ruleset::Classic, Ruleset::Wetlands, Ruleset::Noise]
+    }
+
+    pub fn allowed_neighbors(&self, tile: Tiles) -> Vec<Tiles> {
+        match self {
+            Ruleset::Classic => tile.allowed_neighbors(),
+            Ruleset::Wetlands => match tile {
+                Tiles::Sand => vec![Tiles::Sand, Tiles::Grass, Tiles::Water],
+                Tiles::Grass => Tiles::values(),
+                Tiles::Water => Tiles::values(),
+                Tiles::Forest => vec![Tiles::Forest, Tiles::Grass, Tiles::Water],
+            },
+            Ruleset::Noise => Tiles::values(),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use bevy::{prelude::*, utils::Duration};
+use bevy_tweening::{lens::*, *};
+
+use super::CellPosition;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum CollapseStyle {
+    /// Models pop in with no animation
+    Instant,
+    /// Models fall into place from above
+    Drop,
+    /// Models grow from nothing
+    Scale,
+    /// Models grow outward from the cell that was picked, following propagation
+    Ripple,
+}
+
+impl CollapseStyle {
+    pub fn values() -> Vec<CollapseStyle> {
+        vec![
+            CollapseStyle::Instant,
+            CollapseStyle::Drop,
+            CollapseStyle::Scale,
+            CollapseStyle::Ripple,
+        ]
+    }
+}
+
+pub struct CollapseAnimation {
+    pub style: CollapseStyle,
+    pub ease: EaseFunction,
+    /// Seconds each model takes to animate
+    pub duration: f32,
+    /// Height models drop from, in cells
+    pub drop_height: f32,
+    /// Seconds of delay per cell away from the picked cell when rippling
+    pub ripple_delay: f32,
+}
+
+impl Default for CollapseAnimation {
+    fn default() -> Self {
+        Self {
+            style: CollapseStyle::Ripple,
+            ease: EaseFunction::QuadraticOut,
+            duration: 0.4,
+            drop_height: 2.0,
+            ripple_delay: 0.08,
+        }
+    }
+}
+
+/// Eases offered by the wave panel
+pub const COLLAPSE_EASES: [(EaseFunction, &str); 6] = [
+    (EaseFunction::QuadraticOut, "Quadratic"),
+    (EaseFunction::CubicOut, "Cubic"),
+    (EaseFunction::SineOut, "Sine"),
+    (EaseFunction::BackOut, "Back"),
+    (EaseFunction::ElasticOut, "Elastic"),
+    (EaseFunction::BounceOut, "Bounce"),
+];
+
+/// Cell whose collapse caused this one to collapse through propagation
+#[derive(Component, Eq, PartialEq, Debug, Copy, Clone)]
+pub struct CollapseOrigin(pub CellPosition);
+
+impl CollapseAnimation {
+    /// Animator bringing a model to its final transform, and the transform to start it at
+    pub fn animate(
+        &self,
+        target: Transform,
+        cell_size: f32,
+        distance: usize,
+    ) -> Option<(Transform, Animator<Transform>)> {
+        let duration = Duration::from_secs_f32(self.duration.max(0.001));
+        let (start, animator) = match self.style {
+            CollapseStyle::Instant => return None,
+            CollapseStyle::Drop => {
+                let start = target.translation + Vec3::Y * self.drop_height * cell_size;
+                let tween = Tween::new(
+                    self.ease,
+                    TweeningType::Once,
+                    duration,
+                    TransformPositionLens {
+                        start,
+                        end: target.translation,
+                    },
+                );
+                (
+                    Transform {
+                        translation: start,
+                        ..target
+                    },
+                    Animator::new(tween),
+                )
+            }
+            CollapseStyle::Scale | CollapseStyle::Ripple => {
+                let tween = Tween::new(
+                    self.ease,
+                    TweeningType::Once,
+                    duration,
+                    TransformScaleLens {
+                        start: Vec3::ZERO,
+                        end: target.scale,
+                    },
+                );
+                let delay = match self.style {
+                    CollapseStyle::Ripple => self.ripple_delay * distance as f32,
+                    _ => 0.0,
+                };
+                let animator = if delay > 0.0 {
+                    Animator::new(Delay::new(Duration::from_secs_f32(delay)).then(tween))
+                } else {
+                    Animator::new(tween)
+                };
+                (
+                    Transform {
+                        scale: Vec3::ZERO,
+                        ..target
+                    },
+                    animator,
+                )
+            }
+        };
+        Some((start, animator))
+    }
+}
+
+/// Number of cells between two positions, diagonals count as one
+pub fn cell_distance(a: &CellPosition, b: &CellPosition) -> usize {
+    let dx = (a.x as isize - b.x as isize).unsigned_abs();
+    let dy = (a.y as isize - b.y as isize).unsigned_abs();
+    dx.max(dy)
+}
@@ -0,0 +1,51 @@
+use bevy::{prelude::*, utils::Duration};
+
+use crate::tiles::Tiles;
+
+use super::CellPosition;
+
+/// A cell settled on a single tile
+#[derive(Debug, Clone)]
+pub struct CellCollapsed {
+    pub entity: Entity,
+    pub position: CellPosition,
+    pub tile: Tiles,
+}
+
+/// Tiles ruled out for a cell, either by collapsing it or by propagation from a neighbor
+#[derive(Debug, Clone)]
+pub struct CellNarrowed {
+    pub position: CellPosition,
+    pub removed: Vec<Tiles>,
+}
+
+/// A cell ran out of tiles, the wave backtracks to an earlier choice
+#[derive(Debug, Clone)]
+pub struct WaveContradiction {
+    pub position: CellPosition,
+}
+
+/// Every cell has collapsed
+#[derive(Debug, Clone)]
+pub struct WaveCompleted {
+    pub seed: u64,
+    pub duration: Duration,
+}
+
+/// Collapse the wave until done, building it first if needed, a seed rebuilds it
+#[derive(Debug, Clone, Default)]
+pub struct StartWave {
+    pub seed: Option<u64>,
+}
+
+/// Collapse a single cell
+#[derive(Debug, Clone)]
+pub struct StepWave;
+
+/// Throw away progress and rebuild the wave with the current settings
+#[derive(Debug, Clone)]
+pub struct ResetWave;
+
+/// Stop collapsing, keeping what is done so far
+#[derive(Debug, Clone)]
+pub struct CancelWave;
@@ -0,0 +1,533 @@
+mod animation;
+mod events;
+mod panel;
+mod visuals;
+
+use crate::{
+    csp::{Compatible, Domain, Narrowed, Problem, Solver, Step, VarId},
+    tiles::{Ruleset, Tiles},
+    GameState,
+};
+use bevy::{prelude::*, utils::Duration};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{fmt::Debug, fmt::Display, marker::PhantomData, process::Output, sync::Arc};
+
+use itertools::Itertools;
+
+pub use animation::*;
+pub use events::*;
+pub use panel::*;
+pub use visuals::*;
+
+/// Size of the grid, changes apply the next time the wave is rebuilt
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GridSettings {
+    pub width: usize,
+    pub height: usize,
+    pub cell_size: f32,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            width: 10,
+            height: 10,
+            cell_size: 1.0,
+        }
+    }
+}
+
+pub struct Wave {
+    pub grid: GridSettings,
+    pub seed: u64,
+    pub ruleset: Ruleset,
+    // cells collapsed per frame while running
+    pub steps_per_frame: usize,
+    solver: Solver,
+    rng: StdRng,
+    // dimensions of the grid as built, the cells and solver variables are laid out by these
+    width: usize,
+    height: usize,
+    cell_size: f32,
+    // cell entity and the tile it shows, by solver variable
+    cells: Vec<Entity>,
+    presented: Vec<Option<Tiles>>,
+    running: bool,
+    pending_steps: usize,
+    started: Option<Duration>,
+    duration: Option<Duration>,
+    completed: bool,
+}
+
+impl FromWorld for Wave {
+    fn from_world(world: &mut World) -> Self {
+        let grid = GridSettings::default();
+        Wave {
+            grid,
+            seed: 0,
+            ruleset: Ruleset::default(),
+            steps_per_frame: 1,
+            solver: Solver::default(),
+            rng: StdRng::seed_from_u64(0),
+            width: grid.width,
+            height: grid.height,
+            cell_size: grid.cell_size,
+            cells: Vec::new(),
+            presented: Vec::new(),
+            running: false,
+            pending_steps: 0,
+            started: None,
+            duration: None,
+            completed: false,
+        }
+    }
+}
+
+pub struct WaveAssets {
+    pub placeholder_mesh: Handle<Mesh>,
+    pub placeholder_material: Handle<StandardMaterial>,
+}
+
+impl FromWorld for WaveAssets {
+    fn from_world(world: &mut World) -> Self {
+        let world = world.cell();
+        let mut meshes = world.get_resource_mut::<Assets<Mesh>>().unwrap();
+        let mut materials = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .unwrap();
+
+        // undecided cells share a small cube until they collapse
+        Self {
+            placeholder_mesh: meshes.add(shape::Cube::new(0.2).into()),
+            placeholder_material: materials.add(Color::BLACK.into()),
+        }
+    }
+}
+
+#[derive(Component, Eq, PartialEq, Debug, Copy, Clone)]
+pub struct CellPosition {
+    pub x: usize,
+    pub y: usize,
+}
+
+#[derive(Component, Clone, PartialEq, Eq, Debug)]
+pub struct CellPossable(Vec<Tiles>);
+
+impl CellPossable {
+    fn from_domain(domain: Domain) -> Self {
+        CellPossable(domain.iter().map(Tiles::from_index).collect())
+    }
+}
+
+#[derive(Component, Clone, PartialEq, Eq, Debug)]
+pub struct CellFixed(Tiles);
+
+// The wave only runs in the overworld, tile visuals also wait on the nature kit
+#[derive(RunCriteriaLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct InOverworld;
+
+pub struct WavePlugin;
+
+impl Plugin for WavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Wave>()
+            .init_resource::<WaveAssets>()
+            .init_resource::<TileVisualSettings>()
+            .init_resource::<CollapseAnimation>()
+            .add_event::<CellCollapsed>()
+            .add_event::<CellNarrowed>()
+            .add_event::<WaveContradiction>()
+            .add_event::<WaveCompleted>()
+            .add_event::<StartWave>()
+            .add_event::<StepWave>()
+            .add_event::<ResetWave>()
+            .add_event::<CancelWave>()
+            .add_system_set(SystemSet::on_enter(GameState::Overworld).with_system(build_wave))
+            .add_system_set(
+                SystemSet::new()
+                    .with_run_criteria(State::on_update(GameState::Overworld).label(InOverworld))
+                    .with_system(wave_panel.before(wave_commands))
+                    .with_system(keyboard_input.before(wave_commands))
+                    .with_system(wave_commands)
+                    .with_system(collapse_wave.after(wave_commands)),
+            )
+            .add_system(
+                spawn_tile_visuals
+                    .with_run_criteria(RunCriteria::pipe(InOverworld, nature_kit_loaded))
+                    .after(collapse_wave),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Overworld).with_system(despawn_wave))
+            // removals from collapse_wave only show up once its commands are applied
+            .add_system_to_stage(CoreStage::PostUpdate, reset_tile_visuals);
+    }
+}
+
+// Lays out a fresh grid each time the overworld is entered
+fn build_wave(
+    mut commands: Commands,
+    mut wave: ResMut<Wave>,
+    wave_assets: Res<WaveAssets>,
+    cells: Query<Entity, With<CellPosition>>,
+) {
+    rebuild_wave(&mut commands, &mut wave, &wave_assets, &cells);
+}
+
+fn despawn_wave(
+    mut commands: Commands,
+    mut wave: ResMut<Wave>,
+    cells: Query<Entity, With<CellPosition>>,
+) {
+    for e in cells.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+    wave.cells.clear();
+    wave.running = false;
+    wave.pending_steps = 0;
+}
+
+fn keyboard_input(input: Res<Input<KeyCode>>, mut step_events: EventWriter<StepWave>) {
+    if input.pressed(KeyCode::Space) {
+        step_events.send(StepWave);
+    }
+}
+
+// Applies the start, step, reset and cancel commands
+pub fn wave_commands(
+    mut commands: Commands,
+    mut wave: ResMut<Wave>,
+    wave_assets: Res<WaveAssets>,
+    cells: Query<Entity, With<CellPosition>>,
+    mut start_events: EventReader<StartWave>,
+    mut step_events: EventReader<StepWave>,
+    mut reset_events: EventReader<ResetWave>,
+    mut cancel_events: EventReader<CancelWave>,
+) {
+    if reset_events.iter().count() > 0 {
+        rebuild_wave(&mut commands, &mut wave, &wave_assets, &cells);
+    }
+
+    for start in start_events.iter() {
+        if let Some(seed) = start.seed {
+            wave.seed = seed;
+        }
+        if start.seed.is_some() || wave.cells.is_empty() {
+            rebuild_wave(&mut commands, &mut wave, &wave_assets, &cells);
+        }
+        wave.running = true;
+    }
+
+    if cancel_events.iter().count() > 0 {
+        wave.running = false;
+        wave.pending_steps = 0;
+    }
+
+    let steps = step_events.iter().count();
+    if steps > 0 {
+        if wave.cells.is_empty() {
+            rebuild_wave(&mut commands, &mut wave, &wave_assets, &cells);
+        }
+        wave.pending_steps += steps;
+    }
+}
+
+// Collapses cells while running or when steps are requested
+pub fn collapse_wave(
+    mut commands: Commands,
+    mut wave: ResMut<Wave>,
+    time: Res<Time>,
+    mut collapsed_events: EventWriter<CellCollapsed>,
+    mut narrowed_events: EventWriter<CellNarrowed>,
+    mut contradiction_events: EventWriter<WaveContradiction>,
+    mut completed_events: EventWriter<WaveCompleted>,
+) {
+    let wave = &mut *wave;
+    let mut steps = wave.pending_steps;
+    if wave.running {
+        steps += wave.steps_per_frame;
+    }
+    wave.pending_steps = 0;
+    if steps == 0 || wave.completed {
+        return;
+    }
+    if wave.started.is_none() {
+        wave.started = Some(time.time_since_startup());
+    }
+
+    for _ in 0..steps {
+        // the solver picks the cell with the fewest options left and propagates the result
+        let step = wave.solver.step(&mut wave.rng);
+        let changes = wave.solver.take_changes();
+        match step {
+            Step::Assigned { var, value } => {
+                for change in changes.iter() {
+                    narrowed_events.send(CellNarrowed {
+                        position: wave.position(change.var),
+                        removed: change.removed.iter().map(Tiles::from_index).collect(),
+                    });
+                }
+                let changed = changes.iter().map(|c| c.var).unique().collect::<Vec<_>>();
+                sync_cells(&mut commands, wave, &changed, Some(var), &mut collapsed_events);
+            }
+            Step::Backtracked { var, at, .. } => {
+                debug!(
+                    "Contradiction at {:?} collapsing {:?}, backtracking",
+                    wave.position(at),
+                    wave.position(var)
+                );
+                contradiction_events.send(WaveContradiction {
+                    position: wave.position(at),
+                });
+                let all = (0..wave.cells.len()).collect::<Vec<_>>();
+                sync_cells(&mut commands, wave, &all, None, &mut collapsed_events);
+            }
+            Step::Solved => {
+                let duration = time.time_since_startup() - wave.started.unwrap_or_default();
+                info!("Wave {} collapsed in {:?}", wave.seed, duration);
+                completed_events.send(WaveCompleted {
+                    seed: wave.seed,
+                    duration,
+                });
+                wave.duration = Some(duration);
+                wave.completed = true;
+                wave.running = false;
+                break;
+            }
+            Step::Failed => {
+                warn!("Wave {} has no solution", wave.seed);
+                wave.running = false;
+                break;
+            }
+        }
+    }
+}
+
+// Copy solver domains onto the cell entities, origin is the cell the solver picked
+fn sync_cells(
+    commands: &mut Commands,
+    wave: &mut Wave,
+    vars: &[VarId],
+    origin: Option<VarId>,
+    collapsed_events: &mut EventWriter<CellCollapsed>,
+) {
+    for &var in vars {
+        let e = wave.cells[var];
+        let domain = wave.solver.domain(var);
+        let tile = domain.value().map(Tiles::from_index);
+        if tile == wave.presented[var] {
+            if tile.is_none() {
+                commands.entity(e).insert(CellPossable::from_domain(domain));
+            }
+            continue;
+        }
+
+        match tile {
+            Some(tile) => {
+                // spawn_tile_visuals picks up the models from here
+                commands
+                    .entity(e)
+                    .remove::<CellPossable>()
+                    .insert(CollapseOrigin(wave.position(origin.unwrap_or(var))))
+                    .insert(CellFixed(tile));
+                collapsed_events.send(CellCollapsed {
+                    entity: e,
+                    position: wave.position(var),
+                    tile,
+                });
+            }
+            None => {
+                commands
+                    .entity(e)
+                    .remove::<CellFixed>()
+                    .insert(CellPossable::from_domain(domain));
+            }
+        }
+        wave.presented[var] = tile;
+    }
+}
+
+// Despawn any existing cells and spawn a fresh grid with a new solver
+fn rebuild_wave(
+    commands: &mut Commands,
+    wave: &mut Wave,
+    wave_assets: &WaveAssets,
+    cells: &Query<Entity, With<CellPosition>>,
+) {
+    for e in cells.iter() {
+        commands.entity(e).despawn_recursive();
+    }
+
+    wave.width = wave.grid.width;
+    wave.height = wave.grid.height;
+    wave.cell_size = wave.grid.cell_size;
+    wave.reset_solver();
+    wave.rng = StdRng::seed_from_u64(wave.seed);
+    wave.running = false;
+    wave.pending_steps = 0;
+    wave.started = None;
+    wave.duration = None;
+    wave.completed = false;
+    wave.presented = vec![None; wave.width * wave.height];
+    let cells = (0..wave.width * wave.height)
+        .map(|var| spawn_cell(commands, wave, wave_assets, wave.position(var)))
+        .collect();
+    wave.cells = cells;
+}
+
+fn spawn_cell(
+    commands: &mut Commands,
+    wave: &Wave,
+    wave_assets: &WaveAssets,
+    pos: CellPosition,
+) -> Entity {
+    let CellPosition { x, y } = pos;
+    commands
+        // Note: TextMesh doesnt expose TextMeshState, so have to add it this way
+        // .spawn_bundle(TextMeshBundle {
+        //     text_mesh:  TextMesh {
+        //         text: format!("({x},{y})"),
+        //         style: style.font_3d_style.clone(),
+        //         ..Default::default()
+        //     },
+        //     transform: Transform::from_xyz(x as f32 * wave.cell_size,0.25,-(y as f32 * wave.cell_size)),
+        //     ..default()
+        // })
+        .spawn_bundle(TransformBundle::from_transform(Transform::from_xyz(
+            x as f32 * wave.cell_size,
+            0.0,
+            -(y as f32 * wave.cell_size),
+        )))
+        .insert(pos)
+        .insert(CellPossable(Tiles::values()))
+        .insert(Name::new(format!("Cell ({x},{y})")))
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(PbrBundle {
+                    mesh: wave_assets.placeholder_mesh.clone(),
+                    material: wave_assets.placeholder_material.clone(),
+                    transform: Transform {
+                        translation: Vec3::new(0.0, 0.1 * wave.cell_size, 0.0),
+                        scale: Vec3::splat(wave.cell_size),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .insert(CellPlaceholder);
+        })
+        .id()
+}
+
+impl Wave {
+    pub fn var(&self, pos: &CellPosition) -> VarId {
+        pos.x * self.height + pos.y
+    }
+
+    pub fn position(&self, var: VarId) -> CellPosition {
+        CellPosition {
+            x: var / self.height,
+            y: var % self.height,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.completed
+    }
+
+    pub fn cell_count(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn collapsed_count(&self) -> usize {
+        self.presented.iter().filter(|t| t.is_some()).count()
+    }
+
+    pub fn contradictions(&self) -> usize {
+        self.solver.stats.contradictions
+    }
+
+    /// Time spent collapsing, up to now if still going
+    pub fn elapsed(&self, now: Duration) -> Duration {
+        match (self.duration, self.started) {
+            (Some(duration), _) => duration,
+            (None, Some(started)) => now - started,
+            _ => Duration::ZERO,
+        }
+    }
+
+    // Express the tile rules as a constraint problem, one variable per cell and a
+    // compatibility constraint between each pair of neighbors
+    fn reset_solver(&mut self) {
+        let tiles = Tiles::values();
+        let supports = Arc::new(
+            tiles
+                .iter()
+                .map(|&t| {
+                    Domain::from_values(
+                        self.ruleset
+                            .allowed_neighbors(t)
+                            .into_iter()
+                            .map(|n| n.index()),
+                    )
+                })
+                .collect::<Vec<_>>(),
+        );
+
+        let mut problem = Problem::new();
+        for _ in 0..self.width * self.height {
+            problem.add_variable(Domain::full(tiles.len()));
+        }
+        for x in 0..self.width {
+            for y in 0..self.height {
+                let pos = CellPosition { x, y };
+                let var = self.var(&pos);
+                for neighbor in self.get_neighbors(&pos) {
+                    let neighbor = self.var(&neighbor);
+                    if neighbor > var {
+                        problem.add_constraint(Compatible::new(var, neighbor, supports.clone()));
+                    }
+                }
+            }
+        }
+        self.solver = Solver::new(problem);
+    }
+
+    pub fn get_neighbors(&self, pos: &CellPosition) -> Vec<CellPosition> {
+        let mut neighbors = Vec::new();
+        for x_offset in -1..2 {
+            for y_offset in -1..2 {
+                if x_offset == 0 && y_offset == 0 {
+                    continue;
+                }
+
+                let x_neighbor = pos.x as i32 + x_offset;
+                let y_neighbor = pos.y as i32 + y_offset;
+                if x_neighbor >= 0
+                    && x_neighbor < self.width as i32
+                    && y_neighbor >= 0
+                    && y_neighbor < self.height as i32
+                {
+                    neighbors.push(CellPosition {
+                        x: x_neighbor as usize,
+                        y: y_neighbor as usize,
+                    });
+                }
+            }
+        }
+        neighbors
+    }
+}
@@ -0,0 +1,115 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{bevy_egui::EguiContext, egui};
+
+use crate::tiles::Ruleset;
+
+use super::{
+    CancelWave, CollapseAnimation, CollapseStyle, ResetWave, StartWave, StepWave, Wave,
+    COLLAPSE_EASES,
+};
+
+// Inspector window to tweak and drive the wave without a recompile
+pub fn wave_panel(
+    mut egui_context: ResMut<EguiContext>,
+    mut wave: ResMut<Wave>,
+    mut animation: ResMut<CollapseAnimation>,
+    time: Res<Time>,
+    mut start_events: EventWriter<StartWave>,
+    mut step_events: EventWriter<StepWave>,
+    mut reset_events: EventWriter<ResetWave>,
+    mut cancel_events: EventWriter<CancelWave>,
+) {
+    egui::Window::new("Wave").show(egui_context.ctx_mut(), |ui| {
+        ui.heading("Grid");
+        ui.add(egui::Slider::new(&mut wave.grid.width, 1..=64).text("Width"));
+        ui.add(egui::Slider::new(&mut wave.grid.height, 1..=64).text("Height"));
+        ui.add(egui::Slider::new(&mut wave.grid.cell_size, 0.25..=4.0).text("Cell size"));
+
+        ui.horizontal(|ui| {
+            ui.label("Seed");
+            ui.add(egui::DragValue::new(&mut wave.seed));
+            if ui.button("Randomise").clicked() {
+                wave.seed = rand::random();
+            }
+        });
+
+        egui::ComboBox::from_label("Ruleset")
+            .selected_text(format!("{:?}", wave.ruleset))
+            .show_ui(ui, |ui| {
+                for ruleset in Ruleset::values() {
+                    ui.selectable_value(&mut wave.ruleset, ruleset, format!("{:?}", ruleset));
+                }
+            });
+        ui.small("Grid, seed and ruleset changes apply on reset");
+
+        ui.separator();
+        ui.heading("Collapse");
+        ui.add(egui::Slider::new(&mut wave.steps_per_frame, 1..=100).text("Cells per frame"));
+
+        ui.horizontal(|ui| {
+            if ui.button("Step").clicked() {
+                step_events.send(StepWave);
+            }
+            if wave.is_running() {
+                if ui.button("Stop").clicked() {
+                    cancel_events.send(CancelWave);
+                }
+            } else if ui.button("Run").clicked() {
+                start_events.send(StartWave::default());
+            }
+            if ui.button("Reset").clicked() {
+                reset_events.send(ResetWave);
+            }
+        });
+
+        ui.separator();
+        ui.heading("Animation");
+        egui::ComboBox::from_label("Style")
+            .selected_text(format!("{:?}", animation.style))
+            .show_ui(ui, |ui| {
+                for style in CollapseStyle::values() {
+                    ui.selectable_value(&mut animation.style, style, format!("{:?}", style));
+                }
+            });
+        if animation.style != CollapseStyle::Instant {
+            let ease_name = COLLAPSE_EASES
+                .iter()
+                .find(|(ease, _)| *ease == animation.ease)
+                .map(|(_, name)| *name)
+                .unwrap_or("Custom");
+            egui::ComboBox::from_label("Easing")
+                .selected_text(ease_name)
+                .show_ui(ui, |ui| {
+                    for (ease, name) in COLLAPSE_EASES {
+                        ui.selectable_value(&mut animation.ease, ease, name);
+                    }
+                });
+            ui.add(egui::Slider::new(&mut animation.duration, 0.05..=2.0).text("Duration"));
+            match animation.style {
+                CollapseStyle::Drop => {
+                    ui.add(egui::Slider::new(&mut animation.drop_height, 0.5..=10.0).text("Drop height"));
+                }
+                CollapseStyle::Ripple => {
+                    ui.add(egui::Slider::new(&mut animation.ripple_delay, 0.0..=0.5).text("Ripple delay"));
+                }
+                _ => {}
+            }
+        }
+
+        ui.separator();
+        ui.heading("Stats");
+        ui.label(format!(
+            "Collapsed: {} / {}",
+            wave.collapsed_count(),
+            wave.cell_count()
+        ));
+        ui.label(format!("Contradictions: {}", wave.contradictions()));
+        ui.label(format!(
+            "Time: {:.2}s",
+            wave.elapsed(time.time_since_startup()).as_secs_f32()
+        ));
+        if wave.is_completed() {
+            ui.label("Completed");
+        }
+    });
+}
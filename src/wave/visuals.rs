@@ -0,0 +1,228 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    f32::consts::{FRAC_PI_2, TAU},
+    hash::{Hash, Hasher},
+};
+
+use bevy::{ecs::schedule::ShouldRun, gltf::Gltf, math::vec3, prelude::*};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{assets::NatureKitAssets, tiles::Tiles};
+
+use super::{cell_distance, CellFixed, CellPosition, CollapseAnimation, CollapseOrigin, Wave};
+
+/// Marker for the cube shown while a cell is still undecided
+#[derive(Component)]
+pub struct CellPlaceholder;
+
+/// Marker for the models spawned once a cell collapses
+#[derive(Component)]
+pub struct TileModel;
+
+pub struct TileVisualSettings {
+    /// Chance [0,1] of each decoration slot getting a prop
+    pub decoration_chance: f32,
+    pub max_decorations: usize,
+    /// (min, max) number of trees in a forest cluster
+    pub forest_trees: (usize, usize),
+    /// Rotate ground tiles by a random multiple of 90 degrees
+    pub rotate_ground: bool,
+    /// Rotate features and props by a random angle
+    pub rotate_props: bool,
+    /// Random scale applied to features and props, 1.0 +/- this
+    pub scale_variation: f32,
+}
+
+impl Default for TileVisualSettings {
+    fn default() -> Self {
+        Self {
+            decoration_chance: 0.4,
+            max_decorations: 3,
+            forest_trees: (1, 3),
+            rotate_ground: true,
+            rotate_props: true,
+            scale_variation: 0.2,
+        }
+    }
+}
+
+// The nature kit only exists once loading is done, until then collapsed cells wait for it
+pub fn nature_kit_loaded(
+    In(should_run): In<ShouldRun>,
+    nature_kit: Option<Res<NatureKitAssets>>,
+) -> ShouldRun {
+    match (should_run, nature_kit) {
+        (ShouldRun::Yes, None) => ShouldRun::No,
+        (ShouldRun::YesAndCheckAgain, None) => ShouldRun::NoAndCheckAgain,
+        (should_run, _) => should_run,
+    }
+}
+
+// Swaps the placeholder of a newly collapsed cell for models from the nature kit
+pub fn spawn_tile_visuals(
+    mut commands: Commands,
+    query: Query<
+        (
+            Entity,
+            &CellFixed,
+            &CellPosition,
+            Option<&CollapseOrigin>,
+            Option<&Children>,
+        ),
+        Added<CellFixed>,
+    >,
+    mut placeholders: Query<&mut Visibility, With<CellPlaceholder>>,
+    models: Query<Entity, With<TileModel>>,
+    nature_kit: Res<NatureKitAssets>,
+    gltfs: Res<Assets<Gltf>>,
+    settings: Res<TileVisualSettings>,
+    animation: Res<CollapseAnimation>,
+    wave: Res<Wave>,
+) {
+    for (e, fixed, pos, origin, children) in query.iter() {
+        // hide the placeholder, and clear models if the cell changed its mind
+        if let Some(children) = children {
+            for child in children.iter() {
+                if let Ok(mut visibility) = placeholders.get_mut(*child) {
+                    visibility.is_visible = false;
+                }
+                if models.get(*child).is_ok() {
+                    commands.entity(*child).despawn_recursive();
+                }
+            }
+        }
+
+        let tile = fixed.0;
+        let mut rng = cell_rng(wave.seed, pos);
+        let mut spawns = Vec::new();
+
+        // ground
+        let ground_rotation = if settings.rotate_ground {
+            Quat::from_rotation_y(rng.gen_range(0..4) as f32 * FRAC_PI_2)
+        } else {
+            Quat::IDENTITY
+        };
+        spawns.push((
+            nature_kit.tile_ground(tile),
+            Transform::from_rotation(ground_rotation),
+        ));
+
+        // features, trees are spread around the tile so a cluster doesn't stack up
+        let features = nature_kit.tile_features(tile);
+        if !features.is_empty() {
+            let (min, max) = settings.forest_trees;
+            let count = rng.gen_range(min..=max.max(min));
+            for i in 0..count {
+                let offset = if count == 1 {
+                    Vec3::ZERO
+                } else {
+                    let angle = (i as f32 / count as f32) * TAU + rng.gen_range(0.0..FRAC_PI_2);
+                    vec3(angle.cos(), 0.0, angle.sin()) * 0.25
+                };
+                spawns.push((
+                    features.choose(&mut rng).unwrap().clone(),
+                    prop_transform(&mut rng, &settings, offset),
+                ));
+            }
+        }
+
+        // optional decorations
+        let decorations = nature_kit.tile_decorations(tile);
+        if !decorations.is_empty() {
+            for _ in 0..settings.max_decorations {
+                if rng.gen::<f32>() >= settings.decoration_chance {
+                    continue;
+                }
+                let offset = vec3(rng.gen_range(-0.4..0.4), 0.0, rng.gen_range(-0.4..0.4));
+                spawns.push((
+                    decorations.choose(&mut rng).unwrap().clone(),
+                    prop_transform(&mut rng, &settings, offset),
+                ));
+            }
+        }
+
+        let distance = origin.map(|o| cell_distance(&o.0, pos)).unwrap_or(0);
+        commands.entity(e).with_children(|parent| {
+            for (handle, mut transform) in spawns {
+                if let Some(scene) = gltf_scene(&gltfs, &handle) {
+                    transform.translation *= wave.cell_size;
+                    transform.scale *= wave.cell_size;
+
+                    let mut model = parent.spawn();
+                    match animation.animate(transform, wave.cell_size, distance) {
+                        Some((start, animator)) => {
+                            model
+                                .insert_bundle(TransformBundle::from_transform(start))
+                                .insert(animator);
+                        }
+                        None => {
+                            model.insert_bundle(TransformBundle::from_transform(transform));
+                        }
+                    }
+                    model
+                        .insert(TileModel)
+                        .insert(Name::new(format!("{:?} Model", tile)))
+                        .with_children(|parent| {
+                            parent.spawn_scene(scene);
+                        });
+                } else {
+                    warn!("Model for {:?} not loaded", tile);
+                }
+            }
+        });
+    }
+}
+
+// Cells the solver un-collapsed while backtracking go back to their placeholder
+pub fn reset_tile_visuals(
+    mut commands: Commands,
+    removed: RemovedComponents<CellFixed>,
+    cells: Query<&Children>,
+    mut placeholders: Query<&mut Visibility, With<CellPlaceholder>>,
+    models: Query<Entity, With<TileModel>>,
+) {
+    for e in removed.iter() {
+        if let Ok(children) = cells.get(e) {
+            for child in children.iter() {
+                if let Ok(mut visibility) = placeholders.get_mut(*child) {
+                    visibility.is_visible = true;
+                }
+                if models.get(*child).is_ok() {
+                    commands.entity(*child).despawn_recursive();
+                }
+            }
+        }
+    }
+}
+
+// The same seed lays out the same landscape, whatever order the cells collapse in
+fn cell_rng(seed: u64, pos: &CellPosition) -> StdRng {
+    let mut hasher = DefaultHasher::new();
+    (seed, pos.x, pos.y).hash(&mut hasher);
+    StdRng::seed_from_u64(hasher.finish())
+}
+
+fn prop_transform(rng: &mut impl Rng, settings: &TileVisualSettings, offset: Vec3) -> Transform {
+    let rotation = if settings.rotate_props {
+        Quat::from_rotation_y(rng.gen_range(0.0..TAU))
+    } else {
+        Quat::IDENTITY
+    };
+    let scale = if settings.scale_variation > 0.0 {
+        1.0 + rng.gen_range(-settings.scale_variation..settings.scale_variation)
+    } else {
+        1.0
+    };
+    Transform {
+        translation: offset,
+        rotation,
+        scale: Vec3::splat(scale),
+    }
+}
+
+fn gltf_scene(gltfs: &Assets<Gltf>, handle: &Handle<Gltf>) -> Option<Handle<Scene>> {
+    let gltf = gltfs.get(handle)?;
+    gltf.default_scene
+        .clone()
+        .or_else(|| gltf.scenes.first().cloned())
+}